@@ -0,0 +1,769 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    error::Error,
+    fmt::Display,
+};
+
+use super::{opcode::OpCode, platform::Platform, state::PROGRAM_START};
+
+// Assembler for the Octo language. Supported are labels (`: name`), calls by
+// bare label name, `:alias`, `:const`, `:macro`, `:org`, `:byte`, `:unpack`,
+// `:call`, conditionals (`if ... then` and `if ... begin ... else ... end`),
+// loops (`loop ... while ... again`), raw data bytes and every instruction in
+// `OpCode`. Instructions outside the target platform are rejected.
+//
+// As in Octo, the program starts with a jump to the `main` label, which is
+// left out when `main` is the first thing in the program.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssembleError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl Display for AssembleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for AssembleError {}
+
+pub fn assemble(source: &str, platform: Platform) -> Result<Vec<u8>, AssembleError> {
+    let mut assembler = Assembler::new(source, platform);
+    assembler.program()?;
+    assembler.finish()
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    text: String,
+    line: usize,
+}
+
+struct Macro {
+    params: Vec<String>,
+    body: Vec<Token>,
+}
+
+// How a label address is patched into an instruction once it is known.
+#[derive(Debug, Clone, Copy)]
+enum Fixup {
+    Addr,           // The low 12 bits of the instruction word
+    Long,           // The 16-bit word following `i := long`
+    UnpackHigh(u8), // The byte of `v0 := n << 4 | addr >> 8`
+    UnpackLow,      // The byte of `v1 := addr & 0xFF`
+}
+
+// Open control flow blocks. Addresses point at jumps patched on close.
+enum Block {
+    If {
+        jump: usize,
+        line: usize,
+    },
+    Else {
+        jump: usize,
+        line: usize,
+    },
+    Loop {
+        start: usize,
+        breaks: Vec<usize>,
+        line: usize,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operand {
+    Register(u8),
+    Byte(u8),
+}
+
+struct Assembler {
+    platform: Platform,
+    tokens: VecDeque<Token>,
+    line: usize,
+    rom: Vec<u8>,
+    here: usize,
+    labels: HashMap<String, u16>,
+    consts: HashMap<String, i64>,
+    aliases: HashMap<String, u8>,
+    macros: HashMap<String, Macro>,
+    fixups: Vec<(usize, Fixup, String, usize)>,
+    blocks: Vec<Block>,
+}
+
+impl Assembler {
+    fn new(source: &str, platform: Platform) -> Self {
+        let tokens = source
+            .lines()
+            .enumerate()
+            .flat_map(|(index, line)| {
+                let code = line.split('#').next().unwrap_or("");
+                code.split_whitespace().map(move |text| Token {
+                    text: text.to_string(),
+                    line: index + 1,
+                })
+            })
+            .collect();
+        Assembler {
+            platform,
+            tokens,
+            line: 1,
+            rom: Vec::new(),
+            here: PROGRAM_START,
+            labels: HashMap::new(),
+            consts: HashMap::new(),
+            aliases: HashMap::new(),
+            macros: HashMap::new(),
+            fixups: Vec::new(),
+            blocks: Vec::new(),
+        }
+    }
+
+    fn error<T>(&self, message: impl Into<String>) -> Result<T, AssembleError> {
+        Err(AssembleError {
+            line: self.line,
+            message: message.into(),
+        })
+    }
+
+    fn next(&mut self) -> Result<String, AssembleError> {
+        match self.tokens.pop_front() {
+            Some(token) => {
+                self.line = token.line;
+                Ok(token.text)
+            }
+            None => self.error("Unexpected end of file"),
+        }
+    }
+
+    fn peek(&self) -> Option<&str> {
+        self.tokens.front().map(|token| token.text.as_str())
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), AssembleError> {
+        let token = self.next()?;
+        if token != expected {
+            return self.error(format!("Expected '{}', found '{}'", expected, token));
+        }
+        Ok(())
+    }
+
+    fn name(&mut self) -> Result<String, AssembleError> {
+        let name = self.next()?;
+        if parse_number(&name).is_some() || register_index(&name).is_some() {
+            return self.error(format!("'{}' cannot be used as a name", name));
+        }
+        Ok(name)
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), AssembleError> {
+        let end = self.here + bytes.len();
+        if end > self.platform.memory_size() {
+            return self.error("Program does not fit in memory");
+        }
+        let offset = self.here - PROGRAM_START;
+        if self.rom.len() < offset + bytes.len() {
+            self.rom.resize(offset + bytes.len(), 0);
+        }
+        self.rom[offset..offset + bytes.len()].copy_from_slice(bytes);
+        self.here = end;
+        Ok(())
+    }
+
+    fn emit(&mut self, opcode: OpCode) -> Result<(), AssembleError> {
+        if !self.platform.supports(&opcode) {
+            return self.error(format!(
+                "'{}' is not supported on {:?}",
+                opcode, self.platform
+            ));
+        }
+        self.write(&opcode.encode())
+    }
+
+    // Checks that a jump emitted for a block can reach `target`, which it
+    // cannot past 0xFFF on XO-CHIP.
+    fn jump_target(&self, target: usize) -> Result<u16, AssembleError> {
+        if target > 0x0FFF {
+            return self.error(format!("Jump target {:#X} is out of range", target));
+        }
+        Ok(target as u16)
+    }
+
+    // Patches the 12-bit address of the instruction at `addr`.
+    fn patch(&mut self, addr: usize, target: usize) -> Result<(), AssembleError> {
+        let target = self.jump_target(target)?;
+        let offset = addr - PROGRAM_START;
+        self.rom[offset] = (self.rom[offset] & 0xF0) | (target >> 8) as u8 & 0x0F;
+        self.rom[offset + 1] = target as u8;
+        Ok(())
+    }
+
+    fn program(&mut self) -> Result<(), AssembleError> {
+        // Reserve the jump to main
+        self.fixups
+            .push((self.here, Fixup::Addr, String::from("main"), 1));
+        self.emit(OpCode::JP(0))?;
+        while !self.tokens.is_empty() {
+            self.statement()?;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<Vec<u8>, AssembleError> {
+        if let Some(block) = self.blocks.last() {
+            let (name, line) = match block {
+                Block::If { line, .. } | Block::Else { line, .. } => ("begin", *line),
+                Block::Loop { line, .. } => ("loop", *line),
+            };
+            self.line = line;
+            return self.error(format!("'{}' is never closed", name));
+        }
+        for (addr, fixup, label, line) in std::mem::take(&mut self.fixups) {
+            self.line = line;
+            let Some(&target) = self.labels.get(&label) else {
+                return self.error(format!("Undefined label '{}'", label));
+            };
+            let offset = addr - PROGRAM_START;
+            match fixup {
+                Fixup::Addr if target > 0x0FFF => {
+                    return self.error(format!("Label '{}' is out of range", label));
+                }
+                Fixup::Addr => self.patch(addr, target as usize)?,
+                Fixup::Long => self.rom[offset..offset + 2].copy_from_slice(&target.to_be_bytes()),
+                Fixup::UnpackHigh(nibble) => self.rom[offset] = nibble << 4 | (target >> 8) as u8,
+                Fixup::UnpackLow => self.rom[offset] = target as u8,
+            }
+        }
+        Ok(self.rom)
+    }
+
+    fn define_label(&mut self, name: String) -> Result<(), AssembleError> {
+        if self.labels.contains_key(&name) {
+            return self.error(format!("Label '{}' is already defined", name));
+        }
+        // Drop the jump to main when nothing comes between it and main
+        if name == "main" && self.here == PROGRAM_START + 2 && self.labels.is_empty() {
+            self.fixups.retain(|(addr, ..)| *addr != PROGRAM_START);
+            self.rom.clear();
+            self.here = PROGRAM_START;
+        }
+        self.labels.insert(name, self.here as u16);
+        Ok(())
+    }
+
+    fn statement(&mut self) -> Result<(), AssembleError> {
+        let token = self.next()?;
+        match token.as_str() {
+            ":" => {
+                let name = self.name()?;
+                self.define_label(name)?;
+            }
+            ":alias" => {
+                let name = self.name()?;
+                let register = self.register()?;
+                self.aliases.insert(name, register);
+            }
+            ":const" => {
+                let name = self.name()?;
+                let value = self.value()?;
+                self.consts.insert(name, value);
+            }
+            ":macro" => self.define_macro()?,
+            ":org" => {
+                let addr = self.value()?;
+                if !(PROGRAM_START as i64..self.platform.memory_size() as i64).contains(&addr) {
+                    return self.error(format!("Address {:#X} is out of range", addr));
+                }
+                self.here = addr as usize;
+            }
+            ":byte" => {
+                let byte = self.byte()?;
+                self.write(&[byte])?;
+            }
+            ":call" => {
+                let addr = self.address(Fixup::Addr)?;
+                self.emit(OpCode::CALL(addr))?;
+            }
+            ":unpack" => {
+                let nibble = self.value()?;
+                if !(0..16).contains(&nibble) {
+                    return self.error("The :unpack nibble must be between 0 and 15");
+                }
+                let label = self.name()?;
+                let line = self.line;
+                let high = self.here + 1;
+                self.emit(OpCode::LDVxByte(0, 0))?;
+                let low = self.here + 1;
+                self.emit(OpCode::LDVxByte(1, 0))?;
+                self.fixups
+                    .push((high, Fixup::UnpackHigh(nibble as u8), label.clone(), line));
+                self.fixups.push((low, Fixup::UnpackLow, label, line));
+            }
+            ":breakpoint" => {
+                self.next()?;
+            }
+            "clear" => self.emit(OpCode::CLS)?,
+            "return" | ";" => self.emit(OpCode::RET)?,
+            "exit" => self.emit(OpCode::EXIT)?,
+            "hires" => self.emit(OpCode::HIGH)?,
+            "lores" => self.emit(OpCode::LOW)?,
+            "scroll-down" => {
+                let n = self.nibble()?;
+                self.emit(OpCode::SCD(n))?;
+            }
+            "scroll-right" => self.emit(OpCode::SCR)?,
+            "scroll-left" => self.emit(OpCode::SCL)?,
+            "audio" => self.emit(OpCode::AUDIO)?,
+            "plane" => {
+                let n = self.nibble()?;
+                self.emit(OpCode::PLANE(n))?;
+            }
+            "jump" => {
+                let addr = self.address(Fixup::Addr)?;
+                self.emit(OpCode::JP(addr))?;
+            }
+            "jump0" => {
+                let addr = self.address(Fixup::Addr)?;
+                self.emit(OpCode::JP0(addr))?;
+            }
+            "native" => {
+                let addr = self.address(Fixup::Addr)?;
+                self.emit(OpCode::SYSADDR(addr))?;
+            }
+            "sprite" => {
+                let x = self.register()?;
+                let y = self.register()?;
+                let n = self.nibble()?;
+                self.emit(if n == 0 {
+                    OpCode::DRWVxVy0(x, y)
+                } else {
+                    OpCode::DRW(x, y, n)
+                })?;
+            }
+            "save" | "load" => {
+                let x = self.register()?;
+                if self.peek() == Some("-") {
+                    self.next()?;
+                    let y = self.register()?;
+                    self.emit(match token.as_str() {
+                        "save" => OpCode::LDIVxVy(x, y),
+                        _ => OpCode::LDVxVyI(x, y),
+                    })?;
+                } else {
+                    self.emit(match token.as_str() {
+                        "save" => OpCode::LDIVx(x),
+                        _ => OpCode::LDVxI(x),
+                    })?;
+                }
+            }
+            "saveflags" => {
+                let x = self.register()?;
+                self.emit(OpCode::LDRVx(x))?;
+            }
+            "loadflags" => {
+                let x = self.register()?;
+                self.emit(OpCode::LDVxR(x))?;
+            }
+            "bcd" => {
+                let x = self.register()?;
+                self.emit(OpCode::LDBVx(x))?;
+            }
+            "delay" | "buzzer" | "pitch" => {
+                self.expect(":=")?;
+                let x = self.register()?;
+                self.emit(match token.as_str() {
+                    "delay" => OpCode::LDDTVx(x),
+                    "buzzer" => OpCode::LDSTVx(x),
+                    _ => OpCode::PITCHVx(x),
+                })?;
+            }
+            "i" => self.index_statement()?,
+            "if" => self.if_statement()?,
+            "else" => match self.blocks.pop() {
+                Some(Block::If { jump, .. }) => {
+                    let line = self.line;
+                    let end = self.here;
+                    self.emit(OpCode::JP(0))?;
+                    self.patch(jump, self.here)?;
+                    self.blocks.push(Block::Else { jump: end, line });
+                }
+                _ => return self.error("'else' without 'if ... begin'"),
+            },
+            "end" => match self.blocks.pop() {
+                Some(Block::If { jump, .. } | Block::Else { jump, .. }) => {
+                    self.patch(jump, self.here)?
+                }
+                _ => return self.error("'end' without 'if ... begin'"),
+            },
+            "loop" => {
+                let line = self.line;
+                self.blocks.push(Block::Loop {
+                    start: self.here,
+                    breaks: Vec::new(),
+                    line,
+                });
+            }
+            "while" => {
+                let jump = self.condition()?;
+                let Some(Block::Loop { breaks, .. }) = self
+                    .blocks
+                    .iter_mut()
+                    .rev()
+                    .find(|block| matches!(block, Block::Loop { .. }))
+                else {
+                    return self.error("'while' outside of a loop");
+                };
+                breaks.push(jump);
+            }
+            "again" => match self.blocks.pop() {
+                Some(Block::Loop { start, breaks, .. }) => {
+                    let start = self.jump_target(start)?;
+                    self.emit(OpCode::JP(start))?;
+                    for jump in breaks {
+                        self.patch(jump, self.here)?;
+                    }
+                }
+                _ => return self.error("'again' without 'loop'"),
+            },
+            _ if self.macros.contains_key(&token) => self.expand_macro(&token)?,
+            _ if register_index(&token).is_some() || self.aliases.contains_key(&token) => {
+                let x = self.resolve_register(&token)?;
+                self.register_statement(x)?;
+            }
+            _ if parse_number(&token).is_some() || self.consts.contains_key(&token) => {
+                let byte = self.to_byte(self.resolve_value(&token)?)?;
+                self.write(&[byte])?;
+            }
+            _ if token.starts_with(':') => {
+                return self.error(format!("Unknown directive '{}'", token))
+            }
+            // Anything else is a call to a label
+            _ => {
+                self.tokens.push_front(Token {
+                    text: token,
+                    line: self.line,
+                });
+                let addr = self.address(Fixup::Addr)?;
+                self.emit(OpCode::CALL(addr))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn define_macro(&mut self) -> Result<(), AssembleError> {
+        let name = self.name()?;
+        let mut params = Vec::new();
+        loop {
+            let token = self.next()?;
+            if token == "{" {
+                break;
+            }
+            params.push(token);
+        }
+        let mut body = Vec::new();
+        let mut depth = 1;
+        loop {
+            let Some(token) = self.tokens.pop_front() else {
+                return self.error(format!("Macro '{}' is never closed", name));
+            };
+            match token.text.as_str() {
+                "{" => depth += 1,
+                "}" => depth -= 1,
+                _ => {}
+            }
+            if depth == 0 {
+                break;
+            }
+            body.push(token);
+        }
+        self.macros.insert(name, Macro { params, body });
+        Ok(())
+    }
+
+    fn expand_macro(&mut self, name: &str) -> Result<(), AssembleError> {
+        let line = self.line;
+        let count = self.macros[name].params.len();
+        let mut args = HashMap::new();
+        for index in 0..count {
+            let arg = self.next()?;
+            args.insert(self.macros[name].params[index].clone(), arg);
+        }
+        // Expanded tokens report the line of the invocation
+        let body: Vec<Token> = self.macros[name]
+            .body
+            .iter()
+            .map(|token| Token {
+                text: args.get(&token.text).unwrap_or(&token.text).clone(),
+                line,
+            })
+            .collect();
+        for token in body.into_iter().rev() {
+            self.tokens.push_front(token);
+        }
+        Ok(())
+    }
+
+    fn index_statement(&mut self) -> Result<(), AssembleError> {
+        let op = self.next()?;
+        match op.as_str() {
+            ":=" => match self.peek() {
+                Some("hex") | Some("bighex") => {
+                    let kind = self.next()?;
+                    let x = self.register()?;
+                    self.emit(match kind.as_str() {
+                        "hex" => OpCode::LDFVx(x),
+                        _ => OpCode::LDHFVx(x),
+                    })
+                }
+                Some("long") => {
+                    self.next()?;
+                    let addr = self.address_at(self.here + 2, Fixup::Long)?;
+                    self.emit(OpCode::LDILong(addr))
+                }
+                _ => {
+                    let addr = self.address(Fixup::Addr)?;
+                    self.emit(OpCode::LDI(addr))
+                }
+            },
+            "+=" => {
+                let x = self.register()?;
+                self.emit(OpCode::ADDIVx(x))
+            }
+            _ => self.error(format!("Unknown operator 'i {}'", op)),
+        }
+    }
+
+    fn register_statement(&mut self, x: u8) -> Result<(), AssembleError> {
+        let op = self.next()?;
+        let rhs = match (op.as_str(), self.peek()) {
+            (":=", Some("key")) => {
+                self.next()?;
+                return self.emit(OpCode::LDVxK(x));
+            }
+            (":=", Some("delay")) => {
+                self.next()?;
+                return self.emit(OpCode::LDVxDT(x));
+            }
+            (":=", Some("random")) => {
+                self.next()?;
+                let byte = self.byte()?;
+                return self.emit(OpCode::RND(x, byte));
+            }
+            _ => self.operand()?,
+        };
+        let opcode = match (op.as_str(), rhs) {
+            (":=", Operand::Byte(byte)) => OpCode::LDVxByte(x, byte),
+            (":=", Operand::Register(y)) => OpCode::LDVxVy(x, y),
+            ("+=", Operand::Byte(byte)) => OpCode::ADDVxByte(x, byte),
+            ("+=", Operand::Register(y)) => OpCode::ADDVxVy(x, y),
+            ("-=", Operand::Byte(byte)) => OpCode::ADDVxByte(x, byte.wrapping_neg()),
+            ("-=", Operand::Register(y)) => OpCode::SUBVxVy(x, y),
+            ("=-", Operand::Register(y)) => OpCode::SUBNVyVx(x, y),
+            ("|=", Operand::Register(y)) => OpCode::ORVxVy(x, y),
+            ("&=", Operand::Register(y)) => OpCode::ANDVxVy(x, y),
+            ("^=", Operand::Register(y)) => OpCode::XORVxVy(x, y),
+            (">>=", Operand::Register(y)) => OpCode::SHRVyVx(x, y),
+            ("<<=", Operand::Register(y)) => OpCode::SHLVyVx(x, y),
+            ("=-" | "|=" | "&=" | "^=" | ">>=" | "<<=", Operand::Byte(_)) => {
+                return self.error(format!("'{}' needs a register operand", op))
+            }
+            _ => return self.error(format!("Unknown operator '{}'", op)),
+        };
+        self.emit(opcode)
+    }
+
+    fn if_statement(&mut self) -> Result<(), AssembleError> {
+        // Look ahead for the keyword after the condition to pick the form
+        let keyword = self
+            .tokens
+            .iter()
+            .take(4)
+            .find(|token| token.text == "then" || token.text == "begin")
+            .map(|token| token.text.clone());
+        match keyword.as_deref() {
+            Some("then") => {
+                self.skip_unless(false)?;
+                self.expect("then")
+            }
+            Some("begin") => {
+                let line = self.line;
+                let jump = self.condition()?;
+                self.expect("begin")?;
+                self.blocks.push(Block::If { jump, line });
+                Ok(())
+            }
+            _ => self.error("Expected 'then' or 'begin' after the condition"),
+        }
+    }
+
+    // Emits a condition followed by a jump taken when it is false and
+    // returns the address of the jump.
+    fn condition(&mut self) -> Result<usize, AssembleError> {
+        self.skip_unless(true)?;
+        let jump = self.here;
+        self.emit(OpCode::JP(0))?;
+        Ok(jump)
+    }
+
+    // Parses `vx OP rhs`, `vx key` or `vx -key` and emits instructions that
+    // skip the next one when the condition is false. With `negate` the next
+    // instruction is skipped when the condition is true instead.
+    fn skip_unless(&mut self, negate: bool) -> Result<(), AssembleError> {
+        let x = self.register()?;
+        let op = self.next()?;
+        let (op, negate) = match op.as_str() {
+            "key" => ("key", negate),
+            "-key" => ("key", !negate),
+            "==" => ("==", negate),
+            "!=" => ("==", !negate),
+            ">" => (">", negate),
+            "<=" => (">", !negate),
+            "<" => ("<", negate),
+            ">=" => ("<", !negate),
+            _ => return self.error(format!("Unknown comparison '{}'", op)),
+        };
+        if op == "key" {
+            return self.emit(if negate {
+                OpCode::SKPVx(x)
+            } else {
+                OpCode::SKNPVx(x)
+            });
+        }
+        let rhs = self.operand()?;
+        if op == "==" {
+            return self.emit(match (rhs, negate) {
+                (Operand::Byte(byte), false) => OpCode::SNEVxByte(x, byte),
+                (Operand::Byte(byte), true) => OpCode::SEVxByte(x, byte),
+                (Operand::Register(y), false) => OpCode::SNEVxVy(x, y),
+                (Operand::Register(y), true) => OpCode::SEVxVy(x, y),
+            });
+        }
+        // Orderings go through VF, leaving VF = 1 when the condition is false
+        self.emit(match rhs {
+            Operand::Byte(byte) => OpCode::LDVxByte(0xF, byte),
+            Operand::Register(y) => OpCode::LDVxVy(0xF, y),
+        })?;
+        self.emit(match op {
+            ">" => OpCode::SUBVxVy(0xF, x),
+            _ => OpCode::SUBNVyVx(0xF, x),
+        })?;
+        self.emit(if negate {
+            OpCode::SEVxByte(0xF, 0)
+        } else {
+            OpCode::SNEVxByte(0xF, 0)
+        })
+    }
+
+    fn resolve_register(&self, name: &str) -> Result<u8, AssembleError> {
+        match register_index(name).or_else(|| self.aliases.get(name).copied()) {
+            Some(register) => Ok(register),
+            None => self.error(format!("Expected a register, found '{}'", name)),
+        }
+    }
+
+    fn register(&mut self) -> Result<u8, AssembleError> {
+        let token = self.next()?;
+        self.resolve_register(&token)
+    }
+
+    fn resolve_value(&self, token: &str) -> Result<i64, AssembleError> {
+        match parse_number(token).or_else(|| self.consts.get(token).copied()) {
+            Some(value) => Ok(value),
+            None => self.error(format!("Expected a number, found '{}'", token)),
+        }
+    }
+
+    fn value(&mut self) -> Result<i64, AssembleError> {
+        let token = self.next()?;
+        self.resolve_value(&token)
+    }
+
+    fn to_byte(&self, value: i64) -> Result<u8, AssembleError> {
+        if !(-128..=255).contains(&value) {
+            return self.error(format!("{} does not fit in a byte", value));
+        }
+        Ok(value as u8)
+    }
+
+    fn byte(&mut self) -> Result<u8, AssembleError> {
+        let value = self.value()?;
+        self.to_byte(value)
+    }
+
+    fn nibble(&mut self) -> Result<u8, AssembleError> {
+        let value = self.value()?;
+        if !(0..16).contains(&value) {
+            return self.error(format!("{} does not fit in a nibble", value));
+        }
+        Ok(value as u8)
+    }
+
+    fn operand(&mut self) -> Result<Operand, AssembleError> {
+        let token = self.next()?;
+        if let Ok(register) = self.resolve_register(&token) {
+            return Ok(Operand::Register(register));
+        }
+        let value = self.resolve_value(&token)?;
+        Ok(Operand::Byte(self.to_byte(value)?))
+    }
+
+    // Parses an address for the instruction emitted next.
+    fn address(&mut self, fixup: Fixup) -> Result<u16, AssembleError> {
+        self.address_at(self.here, fixup)
+    }
+
+    // Parses a number, constant or label. Labels that are not defined yet
+    // are patched at `addr` when the program is complete.
+    fn address_at(&mut self, addr: usize, fixup: Fixup) -> Result<u16, AssembleError> {
+        let token = self.next()?;
+        let limit: u16 = match fixup {
+            Fixup::Long => 0xFFFF,
+            _ => 0x0FFF,
+        };
+        if let Some(&target) = self.labels.get(&token) {
+            if target > limit {
+                return self.error(format!("Label '{}' is out of range", token));
+            }
+            return Ok(target);
+        }
+        if parse_number(&token).is_some() || self.consts.contains_key(&token) {
+            let value = self.resolve_value(&token)?;
+            if !(0..=limit as i64).contains(&value) {
+                return self.error(format!("Address {:#X} is out of range", value));
+            }
+            return Ok(value as u16);
+        }
+        if register_index(&token).is_some() || self.aliases.contains_key(&token) {
+            return self.error(format!("Expected an address, found '{}'", token));
+        }
+        self.fixups.push((addr, fixup, token, self.line));
+        Ok(0)
+    }
+}
+
+fn register_index(token: &str) -> Option<u8> {
+    let digit = token.strip_prefix(['v', 'V'])?;
+    if digit.len() != 1 {
+        return None;
+    }
+    u8::from_str_radix(digit, 16).ok()
+}
+
+// Parses decimal, 0x hexadecimal and 0b binary numbers with an optional
+// minus sign.
+fn parse_number(token: &str) -> Option<i64> {
+    let (negative, digits) = match token.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, token),
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if let Some(binary) = digits.strip_prefix("0b") {
+        i64::from_str_radix(binary, 2).ok()?
+    } else if digits.starts_with(|c: char| c.is_ascii_digit()) {
+        digits.parse().ok()?
+    } else {
+        return None;
+    };
+    Some(if negative { -value } else { value })
+}
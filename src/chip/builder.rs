@@ -0,0 +1,156 @@
+use std::{io::Write, path::PathBuf};
+
+use super::{
+    coverage::Coverage, error::TrapPolicy, history::History, platform::Platform,
+    profiler::Profiler, quirks::Quirks, rewind::RewindBuffer, sanitizer::Sanitizer, Chip,
+};
+
+pub struct ChipBuilder {
+    instructions_per_frame: u64,
+    quirks: Option<Quirks>,
+    platform: Platform,
+    seed: Option<u64>,
+    trap_policy: TrapPolicy,
+    save_path: Option<PathBuf>,
+    rewind: Option<(u32, u32)>,
+    history: Option<u32>,
+    trace: Option<Box<dyn Write>>,
+    profile: bool,
+    coverage: bool,
+    strict: bool,
+    random_memory: bool,
+}
+
+impl ChipBuilder {
+    pub fn new() -> Self {
+        ChipBuilder {
+            instructions_per_frame: 8,
+            quirks: None,
+            platform: Platform::default(),
+            seed: None,
+            trap_policy: TrapPolicy::default(),
+            save_path: None,
+            rewind: None,
+            history: None,
+            trace: None,
+            profile: false,
+            coverage: false,
+            strict: false,
+            random_memory: false,
+        }
+    }
+
+    // Number of instructions executed in each 60 Hz frame.
+    pub fn instructions_per_frame(mut self, instructions_per_frame: u64) -> Self {
+        self.instructions_per_frame = instructions_per_frame;
+        self
+    }
+
+    // Overrides the quirks implied by the platform.
+    pub fn quirks(mut self, quirks: Quirks) -> Self {
+        self.quirks = Some(quirks);
+        self
+    }
+
+    pub fn platform(mut self, platform: Platform) -> Self {
+        self.platform = platform;
+        self
+    }
+
+    // Seeds the CXNN random number generator. A random seed is picked when
+    // none is given; it can be read back with `Chip::seed`.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    // What to do when an instruction traps, halting by default.
+    pub fn trap_policy(mut self, trap_policy: TrapPolicy) -> Self {
+        self.trap_policy = trap_policy;
+        self
+    }
+
+    // Base path of the save state slots, slot n is written to
+    // `<path>.<n>.state`. Save states are disabled without it.
+    pub fn save_path(mut self, save_path: impl Into<PathBuf>) -> Self {
+        self.save_path = Some(save_path.into());
+        self
+    }
+
+    // Keeps `seconds` of rewind history, snapshotting every `interval` frames.
+    pub fn rewind(mut self, seconds: u32, interval: u32) -> Self {
+        self.rewind = Some((seconds, interval));
+        self
+    }
+
+    // Keeps `seconds` of execution history so the debugger can step and run
+    // backwards. Every frame that runs instructions costs a full copy of the
+    // state, about 12 KB or 74 KB on XO-CHIP.
+    pub fn history(mut self, seconds: u32) -> Self {
+        self.history = Some(seconds);
+        self
+    }
+
+    // Logs every executed instruction to `trace`, see `trace::write_line`
+    // for the format.
+    pub fn trace(mut self, trace: impl Write + 'static) -> Self {
+        self.trace = Some(Box::new(trace));
+        self
+    }
+
+    // Collects execution statistics, see `Chip::profiler`.
+    pub fn profile(mut self) -> Self {
+        self.profile = true;
+        self
+    }
+
+    // Records how every byte of memory is used, see `Chip::coverage`.
+    pub fn coverage(mut self) -> Self {
+        self.coverage = true;
+        self
+    }
+
+    // Reports suspicious ROM behavior without stopping it, see
+    // `Chip::sanitizer`.
+    pub fn strict(mut self) -> Self {
+        self.strict = true;
+        self
+    }
+
+    // Fills memory with garbage derived from the seed instead of zeroes on
+    // every reset.
+    pub fn random_memory(mut self) -> Self {
+        self.random_memory = true;
+        self
+    }
+
+    pub fn build(self) -> Chip {
+        let quirks = self
+            .quirks
+            .unwrap_or_else(|| self.platform.default_quirks());
+        let seed = self.seed.unwrap_or_else(rand::random);
+        Chip::new(
+            self.instructions_per_frame,
+            quirks,
+            self.platform,
+            seed,
+            self.trap_policy,
+            self.save_path,
+            self.rewind
+                .map(|(seconds, interval)| RewindBuffer::new(seconds, interval)),
+            self.history.map(History::new),
+            self.trace,
+            self.profile.then(Profiler::new),
+            self.coverage
+                .then(|| Coverage::new(self.platform.memory_size())),
+            self.strict.then(Sanitizer::new),
+            self.random_memory,
+        )
+    }
+}
+
+impl Default for ChipBuilder {
+    fn default() -> Self {
+        ChipBuilder::new()
+    }
+}
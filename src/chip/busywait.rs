@@ -0,0 +1,121 @@
+use super::{
+    opcode::OpCode,
+    state::{AccessKind, ChipState},
+};
+
+// Registers and position at the top of a loop, with the instructions the
+// frame had left at that point.
+struct Snapshot {
+    pc: u16,
+    v: [u8; 16],
+    i: u16,
+    sp: u8,
+    remaining: u64,
+    pure: bool, // Only instructions without side effects ran since
+}
+
+// Spots loops that spin without effect within a frame, such as polling the
+// delay timer until it runs out. The timers and keys only change between
+// frames, so once an iteration of a loop leaves the registers as they were
+// at its top, every further iteration in the frame does the same and they
+// can be skipped without changing the outcome.
+pub(crate) struct BusyWait {
+    top: Option<Snapshot>,
+}
+
+// Instructions that only read memory and registers, or jump.
+fn pure(opcode: &OpCode) -> bool {
+    use OpCode::*;
+
+    matches!(
+        opcode,
+        JP(_)
+            | JP0(_)
+            | SEVxByte(..)
+            | SNEVxByte(..)
+            | SEVxVy(..)
+            | SNEVxVy(..)
+            | SKPVx(_)
+            | SKNPVx(_)
+            | LDVxByte(..)
+            | ADDVxByte(..)
+            | LDVxVy(..)
+            | ORVxVy(..)
+            | ANDVxVy(..)
+            | XORVxVy(..)
+            | ADDVxVy(..)
+            | SUBVxVy(..)
+            | SHRVyVx(..)
+            | SUBNVyVx(..)
+            | SHLVyVx(..)
+            | LDVxDT(_)
+            | LDI(_)
+            | LDILong(_)
+            | ADDIVx(_)
+            | LDFVx(_)
+            | LDHFVx(_)
+            | LDVxI(_)
+            | LDVxVyI(..)
+    )
+}
+
+impl BusyWait {
+    pub fn new() -> Self {
+        BusyWait { top: None }
+    }
+
+    // Forgets the loop being watched, called at the start of every frame.
+    pub fn reset(&mut self) {
+        self.top = None;
+    }
+
+    // Looks at the instruction that just ran at `pc`, with `remaining`
+    // instructions left in the frame. Returns how many of those can be
+    // skipped because they would repeat a loop without effect.
+    pub fn record(
+        &mut self,
+        pc: u16,
+        opcode: Option<OpCode>,
+        state: &ChipState,
+        remaining: u64,
+    ) -> u64 {
+        let Some(opcode) = opcode else {
+            self.top = None;
+            return 0;
+        };
+        if let Some(top) = self.top.as_mut() {
+            let writes = state
+                .accesses
+                .iter()
+                .any(|access| access.kind == AccessKind::Write);
+            top.pure &= pure(&opcode) && !writes;
+        }
+        let OpCode::JP(target) = opcode else {
+            return 0;
+        };
+        if target > pc || state.pc != target {
+            return 0;
+        }
+        // A backward jump closes the loop
+        if let Some(top) = self.top.take() {
+            if top.pure
+                && top.pc == target
+                && top.v == state.v
+                && top.i == state.i
+                && top.sp == state.sp
+            {
+                let period = top.remaining - remaining;
+                return remaining / period * period;
+            }
+        }
+        self.top = Some(Snapshot {
+            pc: target,
+            v: state.v,
+            i: state.i,
+            sp: state.sp,
+            remaining,
+            pure: true,
+        });
+        0
+    }
+}
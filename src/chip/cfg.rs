@@ -0,0 +1,529 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, VecDeque},
+    fmt::Display,
+};
+
+use super::{opcode::OpCode, platform::Platform, state::PROGRAM_START};
+
+// Static control flow analysis of a ROM. Code is found by following every
+// path from the program start through jumps, calls, skips and returns,
+// assuming that calls return to the instruction after them. Bytes that are
+// never reached are considered data.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EdgeKind {
+    Fallthrough,
+    Jump,
+    Skip, // Taken when a skip instruction skips
+}
+
+pub type Edge = (u16, EdgeKind);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Instruction {
+    pub addr: u16,
+    pub length: u16,
+    pub word: u16,
+    pub opcode: Option<OpCode>, // `None` when invalid on the platform
+}
+
+// A run of instructions only entered at the top and left at the bottom.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub start: u16,
+    pub instructions: Vec<Instruction>,
+    pub successors: Vec<Edge>,
+    pub calls: Vec<u16>,
+}
+
+// The program start or a CALL target with the blocks reachable from it
+// without following calls.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    pub entry: u16,
+    pub blocks: Vec<u16>,
+    pub callees: Vec<u16>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Region {
+    Code,
+    Data,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Issue {
+    MidInstructionJump { from: u16, target: u16 }, // Lands inside another instruction
+    OutsideRom { from: u16, target: u16 },         // Leaves the loaded ROM
+    ComputedJump { addr: u16 },                    // BNNN, targets are unknown
+    InvalidInstruction { addr: u16, word: u16 },
+    MachineCodeCall { addr: u16 }, // 0NNN
+    Recursion { function: u16 },
+    StackOverflow { depth: usize, chain: Vec<u16> },
+}
+
+impl Display for Issue {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Issue::MidInstructionJump { from, target } => write!(
+                f,
+                "{:03X}: control flow to {:03X} lands in the middle of an instruction",
+                from, target
+            ),
+            Issue::OutsideRom { from, target } => write!(
+                f,
+                "{:03X}: control flow to {:03X} leaves the ROM",
+                from, target
+            ),
+            Issue::ComputedJump { addr } => {
+                write!(
+                    f,
+                    "{:03X}: computed jump, its targets are not analyzed",
+                    addr
+                )
+            }
+            Issue::InvalidInstruction { addr, word } => {
+                write!(
+                    f,
+                    "{:03X}: invalid instruction {:04X} is reachable",
+                    addr, word
+                )
+            }
+            Issue::MachineCodeCall { addr } => {
+                write!(f, "{:03X}: machine code routine call is reachable", addr)
+            }
+            Issue::Recursion { function } => {
+                write!(
+                    f,
+                    "{:03X}: recursive call, stack depth is unbounded",
+                    function
+                )
+            }
+            Issue::StackOverflow { depth, chain } => {
+                let chain: Vec<String> = chain.iter().map(|addr| format!("{:03X}", addr)).collect();
+                write!(
+                    f,
+                    "call chain {} is {} deep, the stack holds 16",
+                    chain.join(" -> "),
+                    depth
+                )
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cfg {
+    pub blocks: BTreeMap<u16, Block>,
+    pub functions: BTreeMap<u16, Function>,
+    pub max_stack_depth: Option<usize>, // `None` when calls recurse
+    pub map: Vec<(u16, u16, Region)>,   // Inclusive address ranges of the ROM
+    pub issues: Vec<Issue>,
+}
+
+const STACK_SIZE: usize = 16;
+
+pub fn analyze(rom: &[u8], platform: Platform) -> Cfg {
+    let end = PROGRAM_START + rom.len();
+    let byte = |addr: usize| *rom.get(addr.wrapping_sub(PROGRAM_START)).unwrap_or(&0) as u16;
+    let word = |addr: usize| (byte(addr) << 8) | byte(addr + 1);
+
+    // Decodes the instruction at `addr` with its successors and call target
+    let decode = |addr: u16| {
+        let index = addr as usize;
+        let opcode = OpCode::decode(addr, word(index), word(index + 2))
+            .ok()
+            .filter(|opcode| platform.supports(opcode));
+        let length = match opcode {
+            Some(OpCode::LDILong(_)) => 4,
+            _ => 2,
+        };
+        let next = addr.wrapping_add(length);
+        let (successors, call) = match opcode {
+            None | Some(OpCode::JP0(_) | OpCode::SYSADDR(_)) => (vec![], None),
+            Some(OpCode::JP(target)) => (vec![(target, EdgeKind::Jump)], None),
+            Some(OpCode::CALL(target)) => (vec![(next, EdgeKind::Fallthrough)], Some(target)),
+            Some(OpCode::RET | OpCode::EXIT) => (vec![], None),
+            Some(
+                OpCode::SEVxByte(..)
+                | OpCode::SNEVxByte(..)
+                | OpCode::SEVxVy(..)
+                | OpCode::SNEVxVy(..)
+                | OpCode::SKPVx(_)
+                | OpCode::SKNPVx(_),
+            ) => {
+                // Skips step over both words of a long load
+                let skipped = if word(next as usize) == 0xF000 { 4 } else { 2 };
+                (
+                    vec![
+                        (next, EdgeKind::Fallthrough),
+                        (next.wrapping_add(skipped), EdgeKind::Skip),
+                    ],
+                    None,
+                )
+            }
+            Some(_) => (vec![(next, EdgeKind::Fallthrough)], None),
+        };
+        let instruction = Instruction {
+            addr,
+            length,
+            word: word(index),
+            opcode,
+        };
+        (instruction, successors, call)
+    };
+
+    // Decode everything that can be reached before judging overlaps, so the
+    // outcome does not depend on the order addresses are visited in
+    let mut decoded = BTreeMap::new();
+    // Addresses reached by falling through or skipping, besides jumps
+    let mut flow = BTreeSet::from([PROGRAM_START as u16]);
+    let mut queue = VecDeque::from([PROGRAM_START as u16]);
+    while let Some(addr) = queue.pop_front() {
+        if decoded.contains_key(&addr) || !(PROGRAM_START..end).contains(&(addr as usize)) {
+            continue;
+        }
+        let (instruction, successors, call) = decode(addr);
+        for &(target, kind) in &successors {
+            if kind != EdgeKind::Jump {
+                flow.insert(target);
+            }
+            queue.push_back(target);
+        }
+        queue.extend(call);
+        decoded.insert(addr, (instruction, successors, call));
+    }
+
+    // Where instructions overlap, the ones reached by flow win over those
+    // only jumped to, then lower addresses over higher ones
+    let mut order: Vec<u16> = decoded.keys().copied().collect();
+    order.sort_by_key(|addr| (!flow.contains(addr), *addr));
+    let mut claimed = vec![false; rom.len()];
+    let mut overlapping = BTreeSet::new();
+    for addr in order {
+        let offset = addr as usize - PROGRAM_START;
+        let covered = offset..(offset + decoded[&addr].0.length as usize).min(rom.len());
+        if claimed[covered.clone()].contains(&true) {
+            overlapping.insert(addr);
+        } else {
+            claimed[covered].fill(true);
+        }
+    }
+
+    let mut issues = Vec::new();
+    let mut instructions: BTreeMap<u16, Instruction> = BTreeMap::new();
+    // Successors and call target of each instruction
+    let mut edges: HashMap<u16, (Vec<Edge>, Option<u16>)> = HashMap::new();
+    // Start address of the instruction covering each ROM byte
+    let mut owner: Vec<Option<u16>> = vec![None; rom.len()];
+    let mut leaders = BTreeSet::from([PROGRAM_START as u16]);
+    let mut queue = VecDeque::from([(PROGRAM_START as u16, PROGRAM_START as u16)]);
+
+    while let Some((from, addr)) = queue.pop_front() {
+        if instructions.contains_key(&addr) {
+            continue;
+        }
+        let index = addr as usize;
+        if !(PROGRAM_START..end).contains(&index) {
+            issues.push(Issue::OutsideRom { from, target: addr });
+            continue;
+        }
+        if overlapping.contains(&addr) {
+            issues.push(Issue::MidInstructionJump { from, target: addr });
+            continue;
+        }
+        let (instruction, successors, call) = decoded[&addr].clone();
+        let covered = index - PROGRAM_START
+            ..(index - PROGRAM_START + instruction.length as usize).min(rom.len());
+        owner[covered].fill(Some(addr));
+        instructions.insert(addr, instruction);
+        match instruction.opcode {
+            None => issues.push(Issue::InvalidInstruction {
+                addr,
+                word: instruction.word,
+            }),
+            Some(OpCode::JP0(_)) => issues.push(Issue::ComputedJump { addr }),
+            Some(OpCode::SYSADDR(_)) => issues.push(Issue::MachineCodeCall { addr }),
+            Some(_) => {}
+        }
+
+        let next = addr.wrapping_add(instruction.length);
+        let plain = call.is_none() && successors == [(next, EdgeKind::Fallthrough)];
+        if !plain {
+            leaders.extend(successors.iter().map(|&(target, _)| target));
+        }
+        for &(target, _) in &successors {
+            queue.push_back((addr, target));
+        }
+        if let Some(target) = call {
+            leaders.insert(target);
+            queue.push_back((addr, target));
+        }
+        edges.insert(addr, (successors, call));
+    }
+
+    // Split the instructions into basic blocks. A block ends before a leader
+    // and after any instruction that does more than fall through.
+    let mut blocks: BTreeMap<u16, Block> = BTreeMap::new();
+    let mut start = PROGRAM_START as u16;
+    let mut previous = None;
+    for (&addr, instruction) in &instructions {
+        if leaders.contains(&addr) || previous != Some(addr) {
+            start = addr;
+            blocks.insert(
+                addr,
+                Block {
+                    start: addr,
+                    instructions: vec![],
+                    successors: vec![],
+                    calls: vec![],
+                },
+            );
+        }
+        let (successors, call) = &edges[&addr];
+        let block = blocks.get_mut(&start).unwrap();
+        block.instructions.push(*instruction);
+        block.successors = successors.clone();
+        block.calls = call.iter().copied().collect();
+        let next = addr.wrapping_add(instruction.length);
+        let plain = call.is_none() && *successors == [(next, EdgeKind::Fallthrough)];
+        previous = plain.then_some(next);
+    }
+
+    // Group blocks into functions
+    let mut entries = BTreeSet::from([PROGRAM_START as u16]);
+    entries.extend(
+        blocks
+            .values()
+            .flat_map(|block| block.calls.iter().copied()),
+    );
+    let mut functions = BTreeMap::new();
+    for entry in entries
+        .into_iter()
+        .filter(|entry| blocks.contains_key(entry))
+    {
+        let mut seen = BTreeSet::from([entry]);
+        let mut queue = VecDeque::from([entry]);
+        let mut callees = BTreeSet::new();
+        while let Some(start) = queue.pop_front() {
+            let Some(block) = blocks.get(&start) else {
+                continue;
+            };
+            callees.extend(block.calls.iter().filter(|call| blocks.contains_key(call)));
+            for &(target, _) in &block.successors {
+                if blocks.contains_key(&target) && seen.insert(target) {
+                    queue.push_back(target);
+                }
+            }
+        }
+        functions.insert(
+            entry,
+            Function {
+                entry,
+                blocks: seen.into_iter().collect(),
+                callees: callees.into_iter().collect(),
+            },
+        );
+    }
+
+    // Find the deepest call chain from the program start
+    let mut depths = HashMap::new();
+    let mut visiting = BTreeSet::new();
+    let deepest = match functions.contains_key(&(PROGRAM_START as u16)) {
+        true => call_depth(
+            PROGRAM_START as u16,
+            &functions,
+            &mut depths,
+            &mut visiting,
+            &mut issues,
+        ),
+        false => Some(Vec::new()),
+    };
+    let max_stack_depth = deepest.map(|chain| {
+        let depth = chain.len().saturating_sub(1);
+        if depth > STACK_SIZE {
+            issues.push(Issue::StackOverflow { depth, chain });
+        }
+        depth
+    });
+
+    // Collapse the bytes into code and data ranges
+    let mut map: Vec<(u16, u16, Region)> = Vec::new();
+    for (offset, owner) in owner.iter().enumerate() {
+        let addr = (PROGRAM_START + offset) as u16;
+        let region = match owner {
+            Some(_) => Region::Code,
+            None => Region::Data,
+        };
+        match map.last_mut() {
+            Some((_, end, last)) if *last == region => *end = addr,
+            _ => map.push((addr, addr, region)),
+        }
+    }
+
+    Cfg {
+        blocks,
+        functions,
+        max_stack_depth,
+        map,
+        issues,
+    }
+}
+
+// Returns the deepest call chain starting at `function`, or `None` when it
+// can recurse. Results are memoized in `depths`.
+fn call_depth(
+    function: u16,
+    functions: &BTreeMap<u16, Function>,
+    depths: &mut HashMap<u16, Option<Vec<u16>>>,
+    visiting: &mut BTreeSet<u16>,
+    issues: &mut Vec<Issue>,
+) -> Option<Vec<u16>> {
+    if let Some(chain) = depths.get(&function) {
+        return chain.clone();
+    }
+    if !visiting.insert(function) {
+        issues.push(Issue::Recursion { function });
+        return None;
+    }
+    let mut deepest = Some(vec![]);
+    for &callee in &functions[&function].callees {
+        let chain = call_depth(callee, functions, depths, visiting, issues);
+        deepest = match (deepest, chain) {
+            (Some(deepest), Some(chain)) if chain.len() > deepest.len() => Some(chain),
+            (Some(deepest), Some(_)) => Some(deepest),
+            _ => None,
+        };
+    }
+    visiting.remove(&function);
+    let chain = deepest.map(|mut chain| {
+        chain.insert(0, function);
+        chain
+    });
+    depths.insert(function, chain.clone());
+    chain
+}
+
+fn instruction_text(instruction: &Instruction) -> String {
+    match instruction.opcode {
+        Some(opcode) => opcode.to_string(),
+        None => format!("INVALID {:04X}", instruction.word),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+impl Cfg {
+    // Graphviz graph of the basic blocks. Calls are drawn as dotted edges to
+    // the called function.
+    pub fn to_dot(&self) -> String {
+        let mut dot =
+            String::from("digraph cfg {\n    node [shape=box, fontname=\"monospace\"];\n");
+        for block in self.blocks.values() {
+            let mut label = String::new();
+            if block.start == PROGRAM_START as u16 {
+                label += "main:\\l";
+            } else if self.functions.contains_key(&block.start) {
+                label += &format!("sub_{:03X}:\\l", block.start);
+            }
+            for instruction in &block.instructions {
+                label += &format!(
+                    "{:03X}: {}\\l",
+                    instruction.addr,
+                    escape(&instruction_text(instruction))
+                );
+            }
+            dot += &format!("    b{:03X} [label=\"{}\"];\n", block.start, label);
+        }
+        for block in self.blocks.values() {
+            for &(target, kind) in &block.successors {
+                if !self.blocks.contains_key(&target) {
+                    continue;
+                }
+                let style = match kind {
+                    EdgeKind::Fallthrough => "",
+                    EdgeKind::Jump => " [color=blue]",
+                    EdgeKind::Skip => " [style=dashed, label=\"skip\"]",
+                };
+                dot += &format!("    b{:03X} -> b{:03X}{};\n", block.start, target, style);
+            }
+            for &target in &block.calls {
+                if self.blocks.contains_key(&target) {
+                    dot += &format!(
+                        "    b{:03X} -> b{:03X} [style=dotted, label=\"call\"];\n",
+                        block.start, target
+                    );
+                }
+            }
+        }
+        dot += "}\n";
+        dot
+    }
+
+    pub fn to_json(&self) -> String {
+        let list = |items: Vec<String>| format!("[{}]", items.join(", "));
+        let numbers = |addrs: &[u16]| list(addrs.iter().map(u16::to_string).collect());
+
+        let blocks = self.blocks.values().map(|block| {
+            let instructions = block.instructions.iter().map(|instruction| {
+                format!(
+                    "{{\"addr\": {}, \"length\": {}, \"text\": \"{}\"}}",
+                    instruction.addr,
+                    instruction.length,
+                    escape(&instruction_text(instruction))
+                )
+            });
+            let successors = block.successors.iter().map(|(target, kind)| {
+                let kind = match kind {
+                    EdgeKind::Fallthrough => "fallthrough",
+                    EdgeKind::Jump => "jump",
+                    EdgeKind::Skip => "skip",
+                };
+                format!("{{\"target\": {}, \"kind\": \"{}\"}}", target, kind)
+            });
+            format!(
+                "{{\"start\": {}, \"instructions\": {}, \"successors\": {}, \"calls\": {}}}",
+                block.start,
+                list(instructions.collect()),
+                list(successors.collect()),
+                numbers(&block.calls)
+            )
+        });
+        let functions = self.functions.values().map(|function| {
+            format!(
+                "{{\"entry\": {}, \"blocks\": {}, \"callees\": {}}}",
+                function.entry,
+                numbers(&function.blocks),
+                numbers(&function.callees)
+            )
+        });
+        let map = self.map.iter().map(|(start, end, region)| {
+            let region = match region {
+                Region::Code => "code",
+                Region::Data => "data",
+            };
+            format!(
+                "{{\"start\": {}, \"end\": {}, \"kind\": \"{}\"}}",
+                start, end, region
+            )
+        });
+        let issues = self
+            .issues
+            .iter()
+            .map(|issue| format!("\"{}\"", escape(&issue.to_string())));
+        let max_stack_depth = match self.max_stack_depth {
+            Some(depth) => depth.to_string(),
+            None => String::from("null"),
+        };
+        format!(
+            "{{\n  \"blocks\": {},\n  \"functions\": {},\n  \"max_stack_depth\": {},\n  \"map\": {},\n  \"issues\": {}\n}}\n",
+            list(blocks.collect()),
+            list(functions.collect()),
+            max_stack_depth,
+            list(map.collect()),
+            list(issues.collect())
+        )
+    }
+}
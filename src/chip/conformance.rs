@@ -0,0 +1,284 @@
+use std::{
+    fmt::Display,
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+use clap::ValueEnum;
+
+use super::{
+    assembler::assemble,
+    platform::Platform,
+    quirks::{QuirkPreset, Quirks},
+    savestate::rom_hash,
+    script::InputScript,
+    state::ChipState,
+    Chip, RunResult,
+};
+
+// Characters of the four plane combinations in golden images.
+const PIXELS: [char; 4] = ['.', '#', 'o', '@'];
+
+// One line of the manifest: a ROM run for a number of frames under each of
+// a list of quirk presets. An optional case is skipped when its ROM is
+// missing, any other fails.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Case {
+    pub name: String,
+    pub rom: PathBuf,
+    pub platform: Platform,
+    pub presets: Vec<QuirkPreset>,
+    pub frames: u64,
+    pub input: Option<PathBuf>,
+    pub optional: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    Pass,
+    Fail(String),    // Why, with an ASCII diff when the display differs
+    Skipped(String), // The ROM of an optional case is not there
+    Blessed,         // The golden image was (re)written
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaseResult {
+    pub name: String,
+    pub preset: QuirkPreset,
+    pub outcome: Outcome,
+}
+
+impl Display for CaseResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let status = match self.outcome {
+            Outcome::Pass => "PASS",
+            Outcome::Fail(_) => "FAIL",
+            Outcome::Skipped(_) => "SKIP",
+            Outcome::Blessed => "BLESS",
+        };
+        write!(
+            f,
+            "{:<5} {} ({})",
+            status,
+            self.name,
+            preset_name(self.preset)
+        )?;
+        match &self.outcome {
+            Outcome::Fail(reason) => write!(f, "\n{}", reason),
+            Outcome::Skipped(reason) => write!(f, ": {}", reason),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn preset_name(preset: QuirkPreset) -> String {
+    preset
+        .to_possible_value()
+        .map_or_else(String::new, |value| value.get_name().to_string())
+}
+
+// Reads `manifest.txt` in `dir`. Each line holds a name, a ROM path, the
+// platform, comma separated quirk presets, the number of frames, optionally
+// an input script or `-` and finally `optional` for a case whose ROM may be
+// missing, paths being relative to `dir`:
+//
+//     alu   roms/alu.8o         chip8  chip8,schip  300  -
+//     ibm   roms/ibm-logo.ch8   chip8  chip8        60   -  optional
+pub fn load_manifest(dir: &Path) -> io::Result<Vec<Case>> {
+    let source = std::fs::read_to_string(dir.join("manifest.txt"))?;
+    let mut cases = Vec::new();
+    for (index, line) in source.lines().enumerate() {
+        let error = |message: String| {
+            io::Error::new(
+                ErrorKind::InvalidData,
+                format!("manifest.txt line {}: {}", index + 1, message),
+            )
+        };
+        let line = line.split('#').next().unwrap_or("");
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if words.is_empty() {
+            continue;
+        }
+        let &[name, rom, platform, presets, frames, ref rest @ ..] = &words[..] else {
+            return Err(error(String::from(
+                "expected NAME ROM PLATFORM PRESETS FRAMES [INPUT [optional]]",
+            )));
+        };
+        let (input, optional) = match rest {
+            [input @ .., "optional"] => (input, true),
+            input => (input, false),
+        };
+        let platform = Platform::from_str(platform, true).map_err(error)?;
+        let presets = presets
+            .split(',')
+            .map(|preset| QuirkPreset::from_str(preset, true))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(error)?;
+        let frames = frames
+            .parse()
+            .map_err(|_| error(format!("invalid frame count '{}'", frames)))?;
+        let input = match input {
+            [] | ["-"] => None,
+            [input] => Some(dir.join(input)),
+            _ => return Err(error(String::from("too many columns"))),
+        };
+        cases.push(Case {
+            name: name.to_string(),
+            rom: dir.join(rom),
+            platform,
+            presets,
+            frames,
+            input,
+            optional,
+        });
+    }
+    Ok(cases)
+}
+
+// The visible part of the display, one line per row.
+pub fn render(state: &ChipState) -> String {
+    let mut image = String::new();
+    for y in 0..state.display_height() {
+        image
+            .extend((0..state.display_width()).map(|x| PIXELS[(state.pixel(x, y) & 0x3) as usize]));
+        image.push('\n');
+    }
+    image
+}
+
+// Hash of a rendered display, stored in the header of golden images.
+pub fn display_hash(image: &str) -> u64 {
+    rom_hash(image.as_bytes())
+}
+
+// Shows `actual` with the pixels that differ from `expected` marked: `+`
+// lit only in `actual`, `-` lit only in `expected` and `*` lit in both on
+// different planes.
+pub fn ascii_diff(expected: &str, actual: &str) -> String {
+    let expected_rows: Vec<&str> = expected.lines().collect();
+    let actual_rows: Vec<&str> = actual.lines().collect();
+    let size = |rows: &[&str]| (rows.first().map_or(0, |row| row.len()), rows.len());
+    if size(&expected_rows) != size(&actual_rows) {
+        let ((ew, eh), (aw, ah)) = (size(&expected_rows), size(&actual_rows));
+        return format!(
+            "resolution differs, expected {}x{} and got {}x{}\nexpected:\n{}actual:\n{}",
+            ew, eh, aw, ah, expected, actual
+        );
+    }
+    let mut diff = String::new();
+    let mut differing = 0;
+    for (expected_row, actual_row) in expected_rows.iter().zip(&actual_rows) {
+        for (e, a) in expected_row.chars().zip(actual_row.chars()) {
+            diff.push(match (e, a) {
+                _ if e == a => a,
+                ('.', _) => '+',
+                (_, '.') => '-',
+                _ => '*',
+            });
+            differing += (e != a) as usize;
+        }
+        diff.push('\n');
+    }
+    format!(
+        "{} pixels differ (+ extra, - missing, * wrong plane):\n{}",
+        differing, diff
+    )
+}
+
+// Runs one case under `preset` and returns the final display rendered.
+pub fn run_case(case: &Case, preset: QuirkPreset) -> Result<String, String> {
+    let rom = match case.rom.extension() {
+        Some(extension) if extension == "8o" => {
+            let source = std::fs::read_to_string(&case.rom).map_err(|e| e.to_string())?;
+            assemble(&source, case.platform).map_err(|e| e.to_string())?
+        }
+        _ => std::fs::read(&case.rom).map_err(|e| e.to_string())?,
+    };
+    let script = match &case.input {
+        Some(path) => {
+            let source = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+            InputScript::parse(&source).map_err(|e| format!("{}: {}", path.display(), e))?
+        }
+        None => InputScript::default(),
+    };
+    let mut chip = Chip::builder()
+        .platform(case.platform)
+        .quirks(Quirks::preset(preset))
+        .seed(0)
+        .build();
+    chip.load_rom(rom).map_err(|e| e.to_string())?;
+    match chip.run_frames(Some(case.frames), &script) {
+        (RunResult::Halted(err) | RunResult::Break(err), frames) => {
+            Err(format!("trapped after {} frames: {}", frames, err))
+        }
+        _ => Ok(render(chip.state())),
+    }
+}
+
+fn golden_path(dir: &Path, case: &Case, preset: QuirkPreset) -> PathBuf {
+    dir.join("golden")
+        .join(format!("{}.{}.txt", case.name, preset_name(preset)))
+}
+
+// Runs every case of the manifest in `dir` and compares the final display
+// against the golden image in `dir/golden`. With `bless` the golden images
+// are written instead.
+pub fn run_suite(dir: &Path, bless: bool) -> io::Result<Vec<CaseResult>> {
+    let mut results = Vec::new();
+    for case in load_manifest(dir)? {
+        for &preset in &case.presets {
+            let outcome = if !case.rom.exists() {
+                let missing = format!("{} not found", case.rom.display());
+                if case.optional {
+                    Outcome::Skipped(missing)
+                } else {
+                    Outcome::Fail(missing)
+                }
+            } else {
+                match run_case(&case, preset) {
+                    Err(reason) => Outcome::Fail(reason),
+                    Ok(actual) => check(&golden_path(dir, &case, preset), &actual, bless)?,
+                }
+            };
+            results.push(CaseResult {
+                name: case.name.clone(),
+                preset,
+                outcome,
+            });
+        }
+    }
+    Ok(results)
+}
+
+// Golden images start with a `# hash` line followed by the rendered display.
+fn check(path: &Path, actual: &str, bless: bool) -> io::Result<Outcome> {
+    let hash = display_hash(actual);
+    if bless {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, format!("# hash {:016x}\n{}", hash, actual))?;
+        return Ok(Outcome::Blessed);
+    }
+    let golden = match std::fs::read_to_string(path) {
+        Ok(golden) => golden,
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            return Ok(Outcome::Fail(format!(
+                "no golden image at {}, run `selftest --bless` to record it",
+                path.display()
+            )))
+        }
+        Err(e) => return Err(e),
+    };
+    let (header, expected) = golden.split_once('\n').unwrap_or(("", ""));
+    let expected_hash = header.strip_prefix("# hash ").unwrap_or("");
+    if expected_hash == format!("{:016x}", hash) && expected == actual {
+        return Ok(Outcome::Pass);
+    }
+    Ok(Outcome::Fail(format!(
+        "hash {:016x}, expected {}\n{}",
+        hash,
+        expected_hash,
+        ascii_diff(expected, actual)
+    )))
+}
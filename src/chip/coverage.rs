@@ -0,0 +1,163 @@
+use std::fmt::Write;
+
+use super::{
+    cfg::{analyze, Region},
+    disasm::disassemble,
+    platform::Platform,
+    state::{AccessKind, MemoryAccess, PROGRAM_START},
+};
+
+// How a byte of memory was used, as bit flags.
+pub const EXECUTED: u8 = 1 << 0; // Fetched as part of an instruction
+pub const SPRITE: u8 = 1 << 1; // Read as sprite data by DXYN
+pub const READ: u8 = 1 << 2; // Read by FX65, FX33 style loads and the like
+pub const WRITTEN: u8 = 1 << 3; // Written by FX55, FX33 and the like
+
+// Memory coverage collected from the access log of every executed
+// instruction.
+#[derive(Debug, Clone)]
+pub struct Coverage {
+    flags: Vec<u8>,
+    hits: Vec<u32>, // Executions of the instruction starting at each address
+}
+
+impl Coverage {
+    pub fn new(memory_size: usize) -> Self {
+        Coverage {
+            flags: vec![0; memory_size],
+            hits: vec![0; memory_size],
+        }
+    }
+
+    pub(crate) fn record(&mut self, accesses: &[MemoryAccess]) {
+        let mut first_fetch = true;
+        for access in accesses {
+            let addr = access.addr as usize;
+            let Some(flags) = self.flags.get_mut(addr) else {
+                continue;
+            };
+            *flags |= match access.kind {
+                AccessKind::Fetch => EXECUTED,
+                AccessKind::SpriteRead => SPRITE,
+                AccessKind::Read => READ,
+                AccessKind::Write => WRITTEN,
+            };
+            if access.kind == AccessKind::Fetch && first_fetch {
+                first_fetch = false;
+                self.hits[addr] = self.hits[addr].saturating_add(1);
+            }
+        }
+    }
+
+    pub fn flags(&self, addr: u16) -> u8 {
+        *self.flags.get(addr as usize).unwrap_or(&0)
+    }
+
+    pub fn hits(&self, addr: u16) -> u32 {
+        *self.hits.get(addr as usize).unwrap_or(&0)
+    }
+
+    // Disassembly of the ROM with a column showing how each line was used:
+    // X executed, S sprite data, R read, W written. Code that the static
+    // analysis can reach but was never executed is listed at the end.
+    pub fn annotate(&self, rom: &[u8], platform: Platform) -> String {
+        let disassembly = disassemble(rom, platform);
+        let mut report = String::new();
+        let count = |flag: u8| {
+            (0..rom.len())
+                .filter(|offset| self.flags((PROGRAM_START + offset) as u16) & flag != 0)
+                .count()
+        };
+        let untouched = (0..rom.len())
+            .filter(|offset| self.flags((PROGRAM_START + offset) as u16) == 0)
+            .count();
+        let _ = writeln!(
+            report,
+            "# {} bytes: {} executed, {} sprite data, {} read, {} written, {} untouched",
+            rom.len(),
+            count(EXECUTED),
+            count(SPRITE),
+            count(READ),
+            count(WRITTEN),
+            untouched
+        );
+        for line in &disassembly.lines {
+            if let Some(label) = disassembly.labels.get(&line.addr) {
+                let _ = writeln!(report, "{}:", label);
+            }
+            let flags = (0..line.bytes.len() as u16)
+                .fold(0, |flags, offset| flags | self.flags(line.addr + offset));
+            let column: String = [(EXECUTED, 'X'), (SPRITE, 'S'), (READ, 'R'), (WRITTEN, 'W')]
+                .iter()
+                .map(|&(flag, c)| if flags & flag != 0 { c } else { '-' })
+                .collect();
+            let hits = match self.hits(line.addr) {
+                0 => String::new(),
+                hits => hits.to_string(),
+            };
+            let _ = writeln!(
+                report,
+                "{} {:>9}  {}",
+                column,
+                hits,
+                disassembly.format_line(line)
+            );
+        }
+
+        let dead = self.dead_code(rom, platform);
+        if !dead.is_empty() {
+            let _ = writeln!(report, "\n# Reachable code that never executed:");
+            for (start, end) in dead {
+                let _ = writeln!(report, "#   {:03X}-{:03X}", start, end);
+            }
+        }
+        report
+    }
+
+    // Inclusive address ranges that the control flow analysis marks as code
+    // but that were never fetched.
+    pub fn dead_code(&self, rom: &[u8], platform: Platform) -> Vec<(u16, u16)> {
+        let mut dead: Vec<(u16, u16)> = Vec::new();
+        for (start, end, region) in analyze(rom, platform).map {
+            if region != Region::Code {
+                continue;
+            }
+            for addr in start..=end {
+                if self.flags(addr) & EXECUTED != 0 {
+                    continue;
+                }
+                match dead.last_mut() {
+                    Some((_, last)) if *last + 1 == addr => *last = addr,
+                    _ => dead.push((addr, addr)),
+                }
+            }
+        }
+        dead
+    }
+
+    // Coverage in the lcov tracefile format. Each instruction of the
+    // disassembly is reported as a line numbered by its address.
+    pub fn to_lcov(&self, rom: &[u8], platform: Platform, name: &str) -> String {
+        let disassembly = disassemble(rom, platform);
+        let code = analyze(rom, platform);
+        let is_code = |addr: u16| {
+            code.map.iter().any(|&(start, end, region)| {
+                region == Region::Code && (start..=end).contains(&addr)
+            })
+        };
+        let mut lcov = format!("TN:\nSF:{}\n", name);
+        let (mut found, mut hit) = (0, 0);
+        for line in &disassembly.lines {
+            let hits = self.hits(line.addr);
+            // Only count lines that are code, statically or by executing them
+            if hits == 0 && !is_code(line.addr) {
+                continue;
+            }
+            found += 1;
+            hit += (hits > 0) as usize;
+            let _ = writeln!(lcov, "DA:{},{}", line.addr, hits);
+        }
+        let _ = write!(lcov, "LF:{}\nLH:{}\nend_of_record\n", found, hit);
+        lcov
+    }
+}
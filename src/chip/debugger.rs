@@ -0,0 +1,231 @@
+use std::collections::BTreeMap;
+
+use super::expr::Expr;
+use super::state::{AccessKind, ChipState, MemoryAccess};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DebugCommand {
+    Step,                  // Execute a single instruction
+    StepOver,              // Execute a single instruction, running CALLs to completion
+    StepOut,               // Run until the current subroutine returns
+    ToggleBreakpoint(u16), // Set or clear a breakpoint at the address
+    RunTo(u16),            // Run until the PC reaches the address
+    StepBack,              // Undo the last instruction
+    StepBackFrame,         // Go back to the start of the previous frame
+    RunBack,               // Go back to the last breakpoint or watchpoint hit
+}
+
+// Pauses when an instruction reads or writes any address in start..=end.
+// Reads include opcode fetches and sprite data.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Watchpoint {
+    pub start: u16,
+    pub end: u16,
+    pub read: bool,
+    pub write: bool,
+}
+
+impl Watchpoint {
+    pub fn matches(&self, access: &MemoryAccess) -> bool {
+        let in_range = (self.start..=self.end).contains(&access.addr);
+        let kind = match access.kind {
+            AccessKind::Write => self.write,
+            _ => self.read,
+        };
+        in_range && kind
+    }
+}
+
+// The access that triggered a watchpoint and the instruction that made it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WatchHit {
+    pub pc: u16,
+    pub access: MemoryAccess,
+}
+
+// Where a step over, step out or run to cursor should stop.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Target {
+    None,
+    Address { pc: u16, sp: Option<u8> },
+    Return { sp: u8 },
+}
+
+// Pause state, PC breakpoints, watchpoints, watch expressions and pending
+// step targets of a chip. Breakpoints are checked before and watchpoints
+// after every instruction executed by `Chip::step_frame`. A breakpoint with
+// a condition only fires when the condition evaluates to non-zero, or fails
+// to evaluate.
+#[derive(Debug)]
+pub struct Debugger {
+    breakpoints: BTreeMap<u16, Option<Expr>>,
+    watchpoints: Vec<Watchpoint>,
+    watches: Vec<Expr>,
+    watch_hit: Option<WatchHit>,
+    paused: bool,
+    target: Target,
+    skip_breakpoint: bool,
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Debugger {
+            breakpoints: BTreeMap::new(),
+            watchpoints: Vec::new(),
+            watches: Vec::new(),
+            watch_hit: None,
+            paused: false,
+            target: Target::None,
+            skip_breakpoint: false,
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+        self.target = Target::None;
+    }
+
+    // Resumes execution. Breakpoints and targets on the current instruction
+    // are ignored once so resuming from them makes progress.
+    pub fn resume(&mut self) {
+        self.paused = false;
+        self.watch_hit = None;
+        self.skip_breakpoint = true;
+    }
+
+    pub fn breakpoints(&self) -> &BTreeMap<u16, Option<Expr>> {
+        &self.breakpoints
+    }
+
+    pub fn add_breakpoint(&mut self, addr: u16) {
+        self.breakpoints.insert(addr, None);
+    }
+
+    // Adds a breakpoint that only fires when `condition` holds, replacing any
+    // breakpoint already at the address.
+    pub fn add_conditional_breakpoint(&mut self, addr: u16, condition: Expr) {
+        self.breakpoints.insert(addr, Some(condition));
+    }
+
+    pub fn remove_breakpoint(&mut self, addr: u16) {
+        self.breakpoints.remove(&addr);
+    }
+
+    pub fn toggle_breakpoint(&mut self, addr: u16) {
+        if self.breakpoints.remove(&addr).is_none() {
+            self.breakpoints.insert(addr, None);
+        }
+    }
+
+    // Expressions shown alongside the registers while paused.
+    pub fn watches(&self) -> &[Expr] {
+        &self.watches
+    }
+
+    pub fn add_watch(&mut self, expr: Expr) {
+        self.watches.push(expr);
+    }
+
+    pub fn remove_watch(&mut self, index: usize) -> Option<Expr> {
+        (index < self.watches.len()).then(|| self.watches.remove(index))
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.watchpoints.push(watchpoint);
+    }
+
+    pub fn remove_watchpoint(&mut self, index: usize) -> Option<Watchpoint> {
+        (index < self.watchpoints.len()).then(|| self.watchpoints.remove(index))
+    }
+
+    // The access that paused execution, cleared on resume.
+    pub fn watch_hit(&self) -> Option<WatchHit> {
+        self.watch_hit
+    }
+
+    pub(crate) fn set_watch_hit(&mut self, watch_hit: Option<WatchHit>) {
+        self.watch_hit = watch_hit;
+    }
+
+    // The first of `accesses` covered by a watchpoint.
+    pub fn watched_access(&self, accesses: &[MemoryAccess]) -> Option<MemoryAccess> {
+        accesses
+            .iter()
+            .find(|access| self.watchpoints.iter().any(|watch| watch.matches(access)))
+            .copied()
+    }
+
+    // Pauses if any of the accesses made by the instruction at `pc` is
+    // watched. Returns true when a watchpoint was hit.
+    pub fn check_watchpoints(&mut self, pc: u16, accesses: &[MemoryAccess]) -> bool {
+        match self.watched_access(accesses) {
+            Some(access) => {
+                self.watch_hit = Some(WatchHit { pc, access });
+                self.pause();
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Resumes until the PC reaches `pc`, optionally only at stack depth `sp`.
+    pub fn run_to(&mut self, pc: u16, sp: Option<u8>) {
+        self.resume();
+        self.target = Target::Address { pc, sp };
+    }
+
+    // Resumes until the stack is shallower than `sp`.
+    pub fn run_until_return(&mut self, sp: u8) {
+        self.resume();
+        self.target = Target::Return { sp };
+    }
+
+    // Whether a breakpoint, with its condition holding, is set at the PC.
+    pub fn breakpoint_hit(&self, state: &ChipState) -> bool {
+        match self.breakpoints.get(&state.pc) {
+            Some(Some(condition)) => !matches!(condition.eval(state), Ok(0)),
+            Some(None) => true,
+            None => false,
+        }
+    }
+
+    // Whether a breakpoint, watchpoint or step target can stop execution.
+    pub fn is_armed(&self) -> bool {
+        !self.breakpoints.is_empty()
+            || !self.watchpoints.is_empty()
+            || !matches!(self.target, Target::None)
+    }
+
+    // Returns true, pausing if needed, when execution must stop before the
+    // instruction at the current PC.
+    pub fn should_break(&mut self, state: &ChipState) -> bool {
+        if self.paused {
+            return true;
+        }
+        let skip_breakpoint = std::mem::take(&mut self.skip_breakpoint);
+        let hit_target = match self.target {
+            Target::None => false,
+            Target::Address { pc, sp } => state.pc == pc && sp.is_none_or(|sp| state.sp == sp),
+            Target::Return { sp } => state.sp < sp,
+        };
+        if !skip_breakpoint && (hit_target || self.breakpoint_hit(state)) {
+            self.pause();
+            return true;
+        }
+        false
+    }
+}
+
+impl Default for Debugger {
+    fn default() -> Self {
+        Debugger::new()
+    }
+}
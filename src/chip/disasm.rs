@@ -0,0 +1,119 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt::Display,
+};
+
+use super::{opcode::OpCode, platform::Platform, state::PROGRAM_START};
+
+#[derive(Debug, PartialEq)]
+pub enum Item {
+    Instruction(OpCode),
+    Data, // Bytes that do not decode to an instruction of the platform
+}
+
+// One instruction or run of data bytes of a disassembled ROM.
+#[derive(Debug, PartialEq)]
+pub struct Line {
+    pub addr: u16,
+    pub bytes: Vec<u8>,
+    pub item: Item,
+}
+
+// A ROM decoded by a linear sweep from the program start, with labels for
+// every jump and call target that starts one of its lines. Other targets,
+// such as odd addresses or the middle of an instruction, stay numeric.
+#[derive(Debug)]
+pub struct Disassembly {
+    pub lines: Vec<Line>,
+    pub labels: BTreeMap<u16, String>,
+}
+
+pub fn disassemble(rom: &[u8], platform: Platform) -> Disassembly {
+    let word = |offset: usize| -> u16 {
+        let byte = |offset: usize| *rom.get(offset).unwrap_or(&0) as u16;
+        (byte(offset) << 8) | byte(offset + 1)
+    };
+
+    let mut lines = Vec::new();
+    let mut offset = 0;
+    while offset < rom.len() {
+        let addr = (PROGRAM_START + offset) as u16;
+        let decoded = OpCode::decode(addr, word(offset), word(offset + 2))
+            .ok()
+            .filter(|opcode| platform.supports(opcode));
+        let length = match decoded {
+            Some(OpCode::LDILong(_)) => 4,
+            _ => 2,
+        };
+        // A trailing odd byte or a truncated long load is data
+        let (length, item) = match decoded {
+            Some(opcode) if offset + length <= rom.len() => (length, Item::Instruction(opcode)),
+            _ => (2.min(rom.len() - offset), Item::Data),
+        };
+        lines.push(Line {
+            addr,
+            bytes: rom[offset..offset + length].to_vec(),
+            item,
+        });
+        offset += length;
+    }
+
+    // Name call targets first so a subroutine that is also jumped to keeps
+    // its `sub_` label
+    let starts: HashSet<u16> = lines.iter().map(|line| line.addr).collect();
+    let mut labels = BTreeMap::new();
+    for (want_calls, prefix) in [(true, "sub"), (false, "L")] {
+        for line in &lines {
+            let target = match line.item {
+                Item::Instruction(OpCode::CALL(addr)) if want_calls => addr,
+                Item::Instruction(OpCode::JP(addr) | OpCode::JP0(addr)) if !want_calls => addr,
+                _ => continue,
+            };
+            if starts.contains(&target) {
+                labels
+                    .entry(target)
+                    .or_insert_with(|| format!("{}_{:03X}", prefix, target));
+            }
+        }
+    }
+
+    Disassembly { lines, labels }
+}
+
+impl Disassembly {
+    // Formats a line as address, raw bytes and mnemonic, with jump and call
+    // targets replaced by their labels.
+    pub fn format_line(&self, line: &Line) -> String {
+        let raw: Vec<String> = line.bytes.iter().map(|b| format!("{:02X}", b)).collect();
+        let text = match line.item {
+            Item::Instruction(OpCode::JP(addr)) if self.labels.contains_key(&addr) => {
+                format!("JP {}", self.labels[&addr])
+            }
+            Item::Instruction(OpCode::JP0(addr)) if self.labels.contains_key(&addr) => {
+                format!("JP V0, {}", self.labels[&addr])
+            }
+            Item::Instruction(OpCode::CALL(addr)) if self.labels.contains_key(&addr) => {
+                format!("CALL {}", self.labels[&addr])
+            }
+            Item::Instruction(ref opcode) => opcode.to_string(),
+            Item::Data => {
+                let bytes: Vec<String> = line.bytes.iter().map(|b| format!("{:#04X}", b)).collect();
+                format!("db {}", bytes.join(", "))
+            }
+        };
+        format!("{:03X}  {:<11} {}", line.addr, raw.join(" "), text)
+    }
+}
+
+// One line per instruction, with labels on lines of their own.
+impl Display for Disassembly {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for line in &self.lines {
+            if let Some(label) = self.labels.get(&line.addr) {
+                writeln!(f, "{}:", label)?;
+            }
+            writeln!(f, "    {}", self.format_line(line))?;
+        }
+        Ok(())
+    }
+}
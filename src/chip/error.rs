@@ -0,0 +1,73 @@
+use std::{error::Error, fmt::Display};
+
+use clap::ValueEnum;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChipError {
+    InvalidOpcode { pc: u16, opcode: u16 },
+    UnsupportedInstruction { pc: u16, opcode: u16 },
+    StackOverflow { pc: u16, opcode: u16 },
+    StackUnderflow { pc: u16, opcode: u16 },
+    MemoryOutOfBounds { pc: u16, opcode: u16, addr: usize },
+    // The ROM does not fit between the program start and the end of memory.
+    RomTooLarge { size: usize, max: usize },
+}
+
+impl ChipError {
+    pub fn pc(&self) -> u16 {
+        match *self {
+            ChipError::InvalidOpcode { pc, .. }
+            | ChipError::UnsupportedInstruction { pc, .. }
+            | ChipError::StackOverflow { pc, .. }
+            | ChipError::StackUnderflow { pc, .. }
+            | ChipError::MemoryOutOfBounds { pc, .. } => pc,
+            ChipError::RomTooLarge { .. } => 0,
+        }
+    }
+
+    pub fn opcode(&self) -> u16 {
+        match *self {
+            ChipError::InvalidOpcode { opcode, .. }
+            | ChipError::UnsupportedInstruction { opcode, .. }
+            | ChipError::StackOverflow { opcode, .. }
+            | ChipError::StackUnderflow { opcode, .. }
+            | ChipError::MemoryOutOfBounds { opcode, .. } => opcode,
+            ChipError::RomTooLarge { .. } => 0,
+        }
+    }
+}
+
+impl Display for ChipError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ChipError::InvalidOpcode { .. } => write!(f, "Invalid opcode")?,
+            ChipError::UnsupportedInstruction { .. } => {
+                write!(f, "Instruction not supported by the platform")?
+            }
+            ChipError::StackOverflow { .. } => write!(f, "Stack overflow")?,
+            ChipError::StackUnderflow { .. } => write!(f, "Stack underflow")?,
+            ChipError::MemoryOutOfBounds { addr, .. } => {
+                write!(f, "Memory access out of bounds at {:#X}", addr)?
+            }
+            ChipError::RomTooLarge { size, max } => {
+                // Not raised by an instruction, so there is no PC to show
+                return write!(f, "ROM is {} bytes, at most {} fit in memory", size, max);
+            }
+        }
+        write!(f, " ({:#06X} at PC {:#05X})", self.opcode(), self.pc())
+    }
+}
+
+impl Error for ChipError {}
+
+// What the chip does when an instruction traps.
+#[derive(Debug, Clone, Copy, PartialEq, Default, ValueEnum)]
+pub enum TrapPolicy {
+    // Stop executing for good and report the error.
+    #[default]
+    Halt,
+    // Skip the faulting instruction and keep running.
+    Ignore,
+    // Stop in front of the faulting instruction so it can be inspected.
+    Break,
+}
@@ -0,0 +1,346 @@
+use std::{error::Error, fmt::Display};
+
+use super::state::ChipState;
+
+// A small expression language evaluated against a `ChipState`, used for
+// conditional breakpoints and watch expressions. Examples:
+//
+//   v3 == 0x10 && mem[i] > 4
+//   dt == 0
+//   stack[sp - 1] == 0x2A4
+//
+// Values are signed 64-bit integers and comparisons yield 0 or 1. The names
+// `v0`-`vf`, `i`, `pc`, `sp`, `dt` (`delay_timer`) and `st` (`sound_timer`)
+// read registers, while `v[n]`, `stack[n]` and `mem[n]` (`memory[n]`) index
+// into the register file, the stack and memory. Operators follow C
+// precedence: `* / %`, `+ -`, `<< >>`, `< <= > >=`, `== !=`, `&`, `^`, `|`,
+// `&&`, `||`, with unary `-`, `!` and `~`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expr {
+    source: String,
+    node: Node,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExprError(pub String);
+
+impl Display for ExprError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for ExprError {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Register {
+    V(u8),
+    I,
+    Pc,
+    Sp,
+    DelayTimer,
+    SoundTimer,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Array {
+    V,
+    Stack,
+    Memory,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum UnaryOp {
+    Neg,
+    Not,
+    BitNot,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BinaryOp {
+    Mul,
+    Div,
+    Rem,
+    Add,
+    Sub,
+    Shl,
+    Shr,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+    BitAnd,
+    BitXor,
+    BitOr,
+    And,
+    Or,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Number(i64),
+    Register(Register),
+    Index(Array, Box<Node>),
+    Unary(UnaryOp, Box<Node>),
+    Binary(BinaryOp, Box<Node>, Box<Node>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(i64),
+    Ident(String),
+    Op(&'static str),
+}
+
+const OPERATORS: [&str; 24] = [
+    "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "*", "/", "%", "+", "-", "<", ">", "&", "^",
+    "|", "!", "~", "(", ")", "[", "]",
+];
+
+impl Expr {
+    pub fn parse(source: &str) -> Result<Expr, ExprError> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let node = parser.binary(0)?;
+        if let Some(token) = parser.tokens.get(parser.pos) {
+            return Err(ExprError(format!("Unexpected {:?}", token)));
+        }
+        Ok(Expr {
+            source: source.trim().to_string(),
+            node,
+        })
+    }
+
+    pub fn eval(&self, state: &ChipState) -> Result<i64, ExprError> {
+        eval(&self.node, state)
+    }
+}
+
+impl Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, ExprError> {
+    let mut tokens = Vec::new();
+    let mut rest = source.trim_start();
+    while !rest.is_empty() {
+        let first = rest.chars().next().unwrap();
+        if first.is_ascii_digit() {
+            let len = rest
+                .find(|c: char| !c.is_ascii_alphanumeric())
+                .unwrap_or(rest.len());
+            let literal = &rest[..len];
+            let value = match literal.strip_prefix("0x") {
+                Some(hex) => i64::from_str_radix(hex, 16),
+                None => literal.parse(),
+            }
+            .map_err(|_| ExprError(format!("Invalid number {}", literal)))?;
+            tokens.push(Token::Number(value));
+            rest = &rest[len..];
+        } else if first.is_ascii_alphabetic() || first == '_' {
+            let len = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            tokens.push(Token::Ident(rest[..len].to_ascii_lowercase()));
+            rest = &rest[len..];
+        } else {
+            let op = OPERATORS
+                .iter()
+                .find(|op| rest.starts_with(*op))
+                .ok_or_else(|| ExprError(format!("Unexpected character {}", first)))?;
+            tokens.push(Token::Op(op));
+            rest = &rest[op.len()..];
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+// Binary operators from the loosest to the tightest binding level.
+const LEVELS: [&[(&str, BinaryOp)]; 10] = [
+    &[("||", BinaryOp::Or)],
+    &[("&&", BinaryOp::And)],
+    &[("|", BinaryOp::BitOr)],
+    &[("^", BinaryOp::BitXor)],
+    &[("&", BinaryOp::BitAnd)],
+    &[("==", BinaryOp::Eq), ("!=", BinaryOp::Ne)],
+    &[
+        ("<", BinaryOp::Lt),
+        ("<=", BinaryOp::Le),
+        (">", BinaryOp::Gt),
+        (">=", BinaryOp::Ge),
+    ],
+    &[("<<", BinaryOp::Shl), (">>", BinaryOp::Shr)],
+    &[("+", BinaryOp::Add), ("-", BinaryOp::Sub)],
+    &[
+        ("*", BinaryOp::Mul),
+        ("/", BinaryOp::Div),
+        ("%", BinaryOp::Rem),
+    ],
+];
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, op: &str) -> bool {
+        if matches!(self.tokens.get(self.pos), Some(Token::Op(token)) if *token == op) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, op: &str) -> Result<(), ExprError> {
+        if self.eat(op) {
+            Ok(())
+        } else {
+            Err(ExprError(format!("Expected {}", op)))
+        }
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Node, ExprError> {
+        if level == LEVELS.len() {
+            return self.unary();
+        }
+        let mut left = self.binary(level + 1)?;
+        'operators: loop {
+            for (op, binary_op) in LEVELS[level] {
+                if self.eat(op) {
+                    let right = self.binary(level + 1)?;
+                    left = Node::Binary(*binary_op, Box::new(left), Box::new(right));
+                    continue 'operators;
+                }
+            }
+            return Ok(left);
+        }
+    }
+
+    fn unary(&mut self) -> Result<Node, ExprError> {
+        for (op, unary_op) in [
+            ("-", UnaryOp::Neg),
+            ("!", UnaryOp::Not),
+            ("~", UnaryOp::BitNot),
+        ] {
+            if self.eat(op) {
+                return Ok(Node::Unary(unary_op, Box::new(self.unary()?)));
+            }
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Node, ExprError> {
+        match self.next() {
+            Some(Token::Number(value)) => Ok(Node::Number(value)),
+            Some(Token::Op("(")) => {
+                let node = self.binary(0)?;
+                self.expect(")")?;
+                Ok(node)
+            }
+            Some(Token::Ident(name)) => {
+                let array = match name.as_str() {
+                    "v" => Some(Array::V),
+                    "stack" => Some(Array::Stack),
+                    "mem" | "memory" => Some(Array::Memory),
+                    _ => None,
+                };
+                if let Some(array) = array {
+                    self.expect("[")?;
+                    let index = self.binary(0)?;
+                    self.expect("]")?;
+                    return Ok(Node::Index(array, Box::new(index)));
+                }
+                let register = match name.as_str() {
+                    "i" => Register::I,
+                    "pc" => Register::Pc,
+                    "sp" => Register::Sp,
+                    "dt" | "delay_timer" => Register::DelayTimer,
+                    "st" | "sound_timer" => Register::SoundTimer,
+                    _ => match name.strip_prefix('v').map(|x| u8::from_str_radix(x, 16)) {
+                        Some(Ok(x)) if x < 16 && name.len() == 2 => Register::V(x),
+                        _ => return Err(ExprError(format!("Unknown name {}", name))),
+                    },
+                };
+                Ok(Node::Register(register))
+            }
+            Some(token) => Err(ExprError(format!("Unexpected {:?}", token))),
+            None => Err(ExprError(String::from("Unexpected end of expression"))),
+        }
+    }
+}
+
+fn eval(node: &Node, state: &ChipState) -> Result<i64, ExprError> {
+    Ok(match node {
+        Node::Number(value) => *value,
+        Node::Register(register) => match register {
+            Register::V(x) => state.v[*x as usize] as i64,
+            Register::I => state.i as i64,
+            Register::Pc => state.pc as i64,
+            Register::Sp => state.sp as i64,
+            Register::DelayTimer => state.delay_timer as i64,
+            Register::SoundTimer => state.sound_timer as i64,
+        },
+        Node::Index(array, index) => {
+            let index = eval(index, state)?;
+            let value = usize::try_from(index).ok().and_then(|index| match array {
+                Array::V => state.v.get(index).map(|&v| v as i64),
+                Array::Stack => state.stack.get(index).map(|&addr| addr as i64),
+                Array::Memory => state.memory.get(index).map(|&byte| byte as i64),
+            });
+            value.ok_or_else(|| ExprError(format!("Index {} out of bounds", index)))?
+        }
+        Node::Unary(op, operand) => {
+            let value = eval(operand, state)?;
+            match op {
+                UnaryOp::Neg => value.wrapping_neg(),
+                UnaryOp::Not => (value == 0) as i64,
+                UnaryOp::BitNot => !value,
+            }
+        }
+        // Short-circuit the logical operators
+        Node::Binary(BinaryOp::And, left, right) => {
+            (eval(left, state)? != 0 && eval(right, state)? != 0) as i64
+        }
+        Node::Binary(BinaryOp::Or, left, right) => {
+            (eval(left, state)? != 0 || eval(right, state)? != 0) as i64
+        }
+        Node::Binary(op, left, right) => {
+            let (left, right) = (eval(left, state)?, eval(right, state)?);
+            match op {
+                BinaryOp::Mul => left.wrapping_mul(right),
+                BinaryOp::Div | BinaryOp::Rem if right == 0 => {
+                    return Err(ExprError(String::from("Division by zero")))
+                }
+                BinaryOp::Div => left.wrapping_div(right),
+                BinaryOp::Rem => left.wrapping_rem(right),
+                BinaryOp::Add => left.wrapping_add(right),
+                BinaryOp::Sub => left.wrapping_sub(right),
+                BinaryOp::Shl => left.wrapping_shl(right as u32),
+                BinaryOp::Shr => left.wrapping_shr(right as u32),
+                BinaryOp::Lt => (left < right) as i64,
+                BinaryOp::Le => (left <= right) as i64,
+                BinaryOp::Gt => (left > right) as i64,
+                BinaryOp::Ge => (left >= right) as i64,
+                BinaryOp::Eq => (left == right) as i64,
+                BinaryOp::Ne => (left != right) as i64,
+                BinaryOp::BitAnd => left & right,
+                BinaryOp::BitXor => left ^ right,
+                BinaryOp::BitOr => left | right,
+                BinaryOp::And | BinaryOp::Or => unreachable!(),
+            }
+        }
+    })
+}
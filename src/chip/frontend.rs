@@ -0,0 +1,50 @@
+use std::error::Error;
+
+use super::{debugger::DebugCommand, state::ChipState, Chip};
+
+pub enum KeyboardEvent {
+    State([bool; 16]),
+    Exit,
+    Reset,
+    Pause,
+    SaveState(u8),
+    LoadState(u8),
+    Rewind,
+    Debug(DebugCommand),
+}
+
+// Receives the framebuffer and any auxiliary state the host wants to show.
+pub trait VideoSink {
+    fn draw(&mut self, state: &ChipState) -> Result<(), Box<dyn Error>>;
+
+    // Called once per run loop iteration with the full state, used by
+    // frontends that display the keypad, timers or other debug information.
+    fn draw_status(&mut self, _state: &ChipState) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    // Called once per run loop iteration to show the debugger, which is
+    // typically only visible while the chip is paused.
+    fn draw_debugger(&mut self, _chip: &Chip) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    // Shows a one-line notification such as the result of saving a state.
+    fn show_message(&mut self, _message: &str) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+}
+
+// Supplies the keypad state and host control events.
+pub trait InputSource {
+    fn get_key(&mut self) -> Result<KeyboardEvent, Box<dyn Error>>;
+}
+
+// Plays the buzzer. `None` silences it.
+pub trait AudioSink {
+    fn set_tone(&mut self, frequency: Option<u16>);
+}
+
+pub trait Frontend: VideoSink + InputSource + AudioSink {}
+
+impl<T: VideoSink + InputSource + AudioSink> Frontend for T {}
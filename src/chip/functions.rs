@@ -1,293 +1,524 @@
 use crate::chip::state::ChipState;
 
-use super::state::KeyboardHalt;
+use super::{
+    error::ChipError,
+    state::{
+        AccessKind, KeyboardHalt, MemoryAccess, BIG_FONT_ADDR, FONT_ADDR, HIRES_HEIGHT, HIRES_WIDTH,
+    },
+};
+
+// Why an instruction could not complete. `Chip` attaches the PC and opcode to
+// turn it into a `ChipError`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fault {
+    UnsupportedInstruction,
+    StackOverflow,
+    StackUnderflow,
+    MemoryOutOfBounds(usize),
+}
+
+impl Fault {
+    pub fn at(self, pc: u16, opcode: u16) -> ChipError {
+        match self {
+            Fault::UnsupportedInstruction => ChipError::UnsupportedInstruction { pc, opcode },
+            Fault::StackOverflow => ChipError::StackOverflow { pc, opcode },
+            Fault::StackUnderflow => ChipError::StackUnderflow { pc, opcode },
+            Fault::MemoryOutOfBounds(addr) => ChipError::MemoryOutOfBounds { pc, opcode, addr },
+        }
+    }
+}
 
-pub fn cls(state: &mut ChipState) -> Result<(), String> {
-    state.display = [0; 64 * 32];
+// All memory accesses made by instructions go through `read` and `write` so
+// they are recorded in `ChipState::accesses`.
+fn read(state: &mut ChipState, addr: usize, kind: AccessKind) -> Result<u8, Fault> {
+    let value = state
+        .memory
+        .get(addr)
+        .copied()
+        .ok_or(Fault::MemoryOutOfBounds(addr))?;
+    state.accesses.push(MemoryAccess {
+        kind,
+        addr: addr as u16,
+        old: value,
+        new: value,
+    });
+    Ok(value)
+}
+
+fn write(state: &mut ChipState, addr: usize, value: u8) -> Result<(), Fault> {
+    let byte = state
+        .memory
+        .get_mut(addr)
+        .ok_or(Fault::MemoryOutOfBounds(addr))?;
+    let old = *byte;
+    *byte = value;
+    state.accesses.push(MemoryAccess {
+        kind: AccessKind::Write,
+        addr: addr as u16,
+        old,
+        new: value,
+    });
+    Ok(())
+}
+
+pub fn cls(state: &mut ChipState) -> Result<(), Fault> {
+    for pixel in state.display.iter_mut() {
+        *pixel &= !state.plane;
+    }
+    state.draw_flag = true;
     Ok(())
 }
 
-pub fn ret(state: &mut ChipState) -> Result<(), String> {
-    state.pc = state.stack[state.sp as usize];
+// Skips the next instruction, stepping over both words of a long
+// `F000 NNNN` load so the skip never lands in the middle of it.
+fn skip(state: &mut ChipState) {
+    let next = state.pc.wrapping_add(2) as usize % state.memory.len();
+    if state.memory[next] == 0xF0 && state.memory[(next + 1) % state.memory.len()] == 0x00 {
+        state.pc = state.pc.wrapping_add(4);
+    } else {
+        state.pc = state.pc.wrapping_add(2);
+    }
+}
+
+pub fn ret(state: &mut ChipState) -> Result<(), Fault> {
+    if state.sp == 0 {
+        return Err(Fault::StackUnderflow);
+    }
     state.sp -= 1;
+    state.pc = state.stack[state.sp as usize];
     Ok(())
 }
 
-pub fn sysaddr(state: &mut ChipState, addr: u16) -> Result<(), String> {
-    state.pc = addr;
-    state.jump_flag = true;
-    Ok(())
+// Machine code routines of the original interpreters cannot be emulated.
+pub fn sysaddr(_state: &mut ChipState, _addr: u16) -> Result<(), Fault> {
+    Err(Fault::UnsupportedInstruction)
 }
 
-pub fn jp(state: &mut ChipState, addr: u16) -> Result<(), String> {
+pub fn jp(state: &mut ChipState, addr: u16) -> Result<(), Fault> {
     state.pc = addr;
     state.jump_flag = true;
     Ok(())
 }
 
-pub fn call(state: &mut ChipState, addr: u16) -> Result<(), String> {
-    state.sp += 1;
+pub fn call(state: &mut ChipState, addr: u16) -> Result<(), Fault> {
+    if state.sp as usize >= state.stack.len() {
+        return Err(Fault::StackOverflow);
+    }
     state.stack[state.sp as usize] = state.pc;
+    state.sp += 1;
     state.pc = addr;
     state.jump_flag = true;
     Ok(())
 }
 
-pub fn se_vx_byte(state: &mut ChipState, x: u8, byte: u8) -> Result<(), String> {
+pub fn se_vx_byte(state: &mut ChipState, x: u8, byte: u8) -> Result<(), Fault> {
     if state.v[x as usize] == byte {
-        state.pc += 2;
+        skip(state);
     }
     Ok(())
 }
 
-pub fn sne_vx_byte(state: &mut ChipState, x: u8, byte: u8) -> Result<(), String> {
+pub fn sne_vx_byte(state: &mut ChipState, x: u8, byte: u8) -> Result<(), Fault> {
     if state.v[x as usize] != byte {
-        state.pc += 2;
+        skip(state);
     }
     Ok(())
 }
 
-pub fn se_vx_vy(state: &mut ChipState, x: u8, y: u8) -> Result<(), String> {
+pub fn se_vx_vy(state: &mut ChipState, x: u8, y: u8) -> Result<(), Fault> {
     if state.v[x as usize] == state.v[y as usize] {
-        state.pc += 2;
+        skip(state);
     }
     Ok(())
 }
 
-pub fn ld_vx_byte(state: &mut ChipState, x: u8, byte: u8) -> Result<(), String> {
+pub fn ld_vx_byte(state: &mut ChipState, x: u8, byte: u8) -> Result<(), Fault> {
     state.v[x as usize] = byte;
     Ok(())
 }
 
-pub fn add_vx_byte(state: &mut ChipState, x: u8, byte: u8) -> Result<(), String> {
+pub fn add_vx_byte(state: &mut ChipState, x: u8, byte: u8) -> Result<(), Fault> {
     state.v[x as usize] = state.v[x as usize].wrapping_add(byte);
     Ok(())
 }
 
-pub fn ld_vx_vy(state: &mut ChipState, x: u8, y: u8) -> Result<(), String> {
+pub fn ld_vx_vy(state: &mut ChipState, x: u8, y: u8) -> Result<(), Fault> {
     state.v[x as usize] = state.v[y as usize];
     Ok(())
 }
 
-pub fn or_vx_vy(state: &mut ChipState, x: u8, y: u8) -> Result<(), String> {
-    state.v[0xF] = 0;
+pub fn or_vx_vy(state: &mut ChipState, x: u8, y: u8, vf_reset: bool) -> Result<(), Fault> {
     state.v[x as usize] |= state.v[y as usize];
+    if vf_reset {
+        state.v[0xF] = 0;
+    }
     Ok(())
 }
 
-pub fn and_vx_vy(state: &mut ChipState, x: u8, y: u8) -> Result<(), String> {
-    state.v[0xF] = 0;
+pub fn and_vx_vy(state: &mut ChipState, x: u8, y: u8, vf_reset: bool) -> Result<(), Fault> {
     state.v[x as usize] &= state.v[y as usize];
+    if vf_reset {
+        state.v[0xF] = 0;
+    }
     Ok(())
 }
 
-pub fn xor_vx_vy(state: &mut ChipState, x: u8, y: u8) -> Result<(), String> {
-    state.v[0xF] = 0;
+pub fn xor_vx_vy(state: &mut ChipState, x: u8, y: u8, vf_reset: bool) -> Result<(), Fault> {
     state.v[x as usize] ^= state.v[y as usize];
+    if vf_reset {
+        state.v[0xF] = 0;
+    }
     Ok(())
 }
 
-pub fn add_vx_vy(state: &mut ChipState, x: u8, y: u8) -> Result<(), String> {
+pub fn add_vx_vy(state: &mut ChipState, x: u8, y: u8) -> Result<(), Fault> {
     let (result, overflow) = state.v[x as usize].overflowing_add(state.v[y as usize]);
     state.v[x as usize] = result;
     state.v[0xF] = overflow as u8;
     Ok(())
 }
 
-pub fn sub_vx_vy(state: &mut ChipState, x: u8, y: u8) -> Result<(), String> {
+pub fn sub_vx_vy(state: &mut ChipState, x: u8, y: u8) -> Result<(), Fault> {
     let (result, borrow) = state.v[x as usize].overflowing_sub(state.v[y as usize]);
     state.v[x as usize] = result;
     state.v[0xF] = (!borrow) as u8;
     Ok(())
 }
 
-pub fn shr(state: &mut ChipState, x: u8, y: u8, shift_quirk: bool) -> Result<(), String> {
-    if !shift_quirk {
-        state.v[x as usize] = state.v[y as usize];
-    }
-    state.v[0xF] = state.v[x as usize] & 0x1;
-    state.v[x as usize] >>= 1;
+// The flag is written last so it wins when VF is the destination, like the
+// other arithmetic instructions.
+pub fn shr(state: &mut ChipState, x: u8, y: u8, shifting: bool) -> Result<(), Fault> {
+    let value = state.v[if shifting { x } else { y } as usize];
+    state.v[x as usize] = value >> 1;
+    state.v[0xF] = value & 0x1;
     Ok(())
 }
 
-pub fn subn_vy_vx(state: &mut ChipState, x: u8, y: u8) -> Result<(), String> {
+pub fn subn_vy_vx(state: &mut ChipState, x: u8, y: u8) -> Result<(), Fault> {
     let (result, borrow) = state.v[y as usize].overflowing_sub(state.v[x as usize]);
     state.v[x as usize] = result;
     state.v[0xF] = (!borrow) as u8;
     Ok(())
 }
 
-pub fn shl(state: &mut ChipState, x: u8, y: u8, shift_quirk: bool) -> Result<(), String> {
-    if !shift_quirk {
-        state.v[x as usize] = state.v[y as usize];
-    }
-    state.v[0xF] = (state.v[x as usize] & 0x80) >> 7;
-    state.v[x as usize] <<= 1;
+pub fn shl(state: &mut ChipState, x: u8, y: u8, shifting: bool) -> Result<(), Fault> {
+    let value = state.v[if shifting { x } else { y } as usize];
+    state.v[x as usize] = value << 1;
+    state.v[0xF] = value >> 7;
     Ok(())
 }
 
-pub fn sne_vx_vy(state: &mut ChipState, x: u8, y: u8) -> Result<(), String> {
+pub fn sne_vx_vy(state: &mut ChipState, x: u8, y: u8) -> Result<(), Fault> {
     if state.v[x as usize] != state.v[y as usize] {
-        state.pc += 2;
+        skip(state);
     }
     Ok(())
 }
 
-pub fn ld_i(state: &mut ChipState, addr: u16) -> Result<(), String> {
+pub fn ld_i(state: &mut ChipState, addr: u16) -> Result<(), Fault> {
     state.i = addr;
     Ok(())
 }
 
-pub fn jp0(state: &mut ChipState, addr: u16) -> Result<(), String> {
-    state.pc = addr + state.v[0] as u16;
+pub fn jp0(state: &mut ChipState, addr: u16, jumping: bool) -> Result<(), Fault> {
+    let x = if jumping { (addr >> 8) & 0xF } else { 0 };
+    state.pc = addr.wrapping_add(state.v[x as usize] as u16);
     state.jump_flag = true;
     Ok(())
 }
 
-pub fn rnd(state: &mut ChipState, x: u8, byte: u8) -> Result<(), String> {
-    state.v[x as usize] = rand::random::<u8>() & byte;
+pub fn rnd(state: &mut ChipState, x: u8, byte: u8, random: u8) -> Result<(), Fault> {
+    state.v[x as usize] = random & byte;
+    Ok(())
+}
+
+pub fn drw(
+    state: &mut ChipState,
+    x: u8,
+    y: u8,
+    n: u8,
+    clipping: bool,
+    display_wait: bool,
+) -> Result<(), Fault> {
+    draw_sprite(state, x, y, 8, n as usize, clipping)?;
+    state.vblank_wait = display_wait;
     Ok(())
 }
 
-pub fn drw(state: &mut ChipState, x: u8, y: u8, n: u8) -> Result<(), String> {
+// Draws a `width` x `height` sprite read from memory at I on every selected
+// plane. Sprites that are 16 pixels wide are stored as two bytes per row and
+// the data for the second plane follows the first. The sprite origin always
+// wraps; pixels past the edges are either clipped or wrapped. In hires mode VF
+// holds the number of rows that collided or were clipped off the bottom,
+// otherwise it is set to 1 on any collision.
+fn draw_sprite(
+    state: &mut ChipState,
+    x: u8,
+    y: u8,
+    width: usize,
+    height: usize,
+    clipping: bool,
+) -> Result<(), Fault> {
     state.draw_flag = true;
-    let x = state.v[x as usize] as usize;
-    let y = state.v[y as usize] as usize;
-    let n = n as usize;
-
-    state.v[0xF] = 0;
-
-    for yline in 0..n {
-        let pixel = state.memory[state.i as usize + yline];
-        for xline in 0..8 {
-            if (pixel & (0x80 >> xline)) != 0 {
-                let _x = (x + xline) % 64;
-                let _y = (y + yline) % 32;
-                if state.display[_y * 64 + _x] == 1 {
-                    state.v[0xF] = 1;
+    let display_width = state.display_width();
+    let display_height = state.display_height();
+    let x = state.v[x as usize] as usize % display_width;
+    let y = state.v[y as usize] as usize % display_height;
+    let bytes_per_row = width / 8;
+
+    let mut collisions = 0;
+    let mut addr = state.i as usize;
+
+    for plane in [1, 2] {
+        if state.plane & plane == 0 {
+            continue;
+        }
+        for yline in 0..height {
+            if clipping && y + yline >= display_height {
+                if state.hires {
+                    collisions += 1;
+                }
+                continue;
+            }
+            let row_addr = addr + yline * bytes_per_row;
+            let mut row = 0u16;
+            for byte in 0..bytes_per_row {
+                row = (row << 8) | read(state, row_addr + byte, AccessKind::SpriteRead)? as u16;
+            }
+            let mut collided = false;
+            for xline in 0..width {
+                if clipping && x + xline >= display_width {
+                    break;
+                }
+                if (row & (1 << (width - 1 - xline))) != 0 {
+                    let _x = (x + xline) % display_width;
+                    let _y = (y + yline) % display_height;
+                    let index = _y * display_width + _x;
+                    if state.display[index] & plane != 0 {
+                        collided = true;
+                    }
+                    state.display[index] ^= plane;
                 }
-                state.display[_y * 64 + _x] ^= 1;
+            }
+            if collided {
+                collisions += 1;
             }
         }
+        addr += height * bytes_per_row;
     }
+
+    state.v[0xF] = if state.hires {
+        collisions
+    } else {
+        (collisions > 0) as u8
+    };
     Ok(())
 }
 
-pub fn skp_vx(state: &mut ChipState, x: u8) -> Result<(), String> {
-    if state.keypad[state.v[x as usize] as usize] {
-        state.pc += 2;
+pub fn skp_vx(state: &mut ChipState, x: u8) -> Result<(), Fault> {
+    if state.keypad[(state.v[x as usize] & 0xF) as usize] {
+        skip(state);
     }
     Ok(())
 }
 
-pub fn sknp_vx(state: &mut ChipState, x: u8) -> Result<(), String> {
-    if !state.keypad[state.v[x as usize] as usize] {
-        state.pc += 2;
+pub fn sknp_vx(state: &mut ChipState, x: u8) -> Result<(), Fault> {
+    if !state.keypad[(state.v[x as usize] & 0xF) as usize] {
+        skip(state);
     }
     Ok(())
 }
 
-pub fn ld_dt_vx(state: &mut ChipState, x: u8) -> Result<(), String> {
+pub fn ld_dt_vx(state: &mut ChipState, x: u8) -> Result<(), Fault> {
     state.delay_timer = state.v[x as usize];
     Ok(())
 }
 
-pub fn ld_vx_dt(state: &mut ChipState, x: u8) -> Result<(), String> {
+pub fn ld_vx_dt(state: &mut ChipState, x: u8) -> Result<(), Fault> {
     state.v[x as usize] = state.delay_timer;
     Ok(())
 }
 
-pub fn ld_st_vx(state: &mut ChipState, x: u8) -> Result<(), String> {
+pub fn ld_st_vx(state: &mut ChipState, x: u8) -> Result<(), Fault> {
     state.sound_timer = state.v[x as usize];
     Ok(())
 }
 
-pub fn add_i_vx(state: &mut ChipState, x: u8) -> Result<(), String> {
-    state.i += state.v[x as usize] as u16;
+pub fn add_i_vx(state: &mut ChipState, x: u8) -> Result<(), Fault> {
+    state.i = state.i.wrapping_add(state.v[x as usize] as u16);
     Ok(())
 }
 
-pub fn ld_f_vx(state: &mut ChipState, x: u8) -> Result<(), String> {
-    state.i = state.v[x as usize] as u16 * 5;
+pub fn ld_f_vx(state: &mut ChipState, x: u8) -> Result<(), Fault> {
+    state.i = FONT_ADDR as u16 + state.v[x as usize] as u16 * 5;
     Ok(())
 }
 
-pub fn ld_b_vx(state: &mut ChipState, x: u8) -> Result<(), String> {
+pub fn ld_b_vx(state: &mut ChipState, x: u8) -> Result<(), Fault> {
     let vx = state.v[x as usize];
-    state.memory[state.i as usize] = vx / 100;
-    state.memory[state.i as usize + 1] = (vx / 10) % 10;
-    state.memory[state.i as usize + 2] = vx % 10;
+    let i = state.i as usize;
+    write(state, i, vx / 100)?;
+    write(state, i + 1, (vx / 10) % 10)?;
+    write(state, i + 2, vx % 10)?;
     Ok(())
 }
 
-pub fn ld_i_vx(state: &mut ChipState, x: u8) -> Result<(), String> {
-    for i in 0..=x {
-        state.memory[state.i as usize] = state.v[i as usize];
-        state.i += 1;
+pub fn ld_i_vx(state: &mut ChipState, x: u8, memory_increment: bool) -> Result<(), Fault> {
+    for i in 0..=x as usize {
+        write(state, state.i as usize + i, state.v[i])?;
+    }
+    if memory_increment {
+        state.i = state.i.wrapping_add(x as u16 + 1);
     }
     Ok(())
 }
 
-pub fn ld_vx_i(state: &mut ChipState, x: u8) -> Result<(), String> {
-    for i in 0..=x {
-        state.v[i as usize] = state.memory[state.i as usize];
-        state.i += 1;
+pub fn ld_vx_i(state: &mut ChipState, x: u8, memory_increment: bool) -> Result<(), Fault> {
+    for i in 0..=x as usize {
+        state.v[i] = read(state, state.i as usize + i, AccessKind::Read)?;
+    }
+    if memory_increment {
+        state.i = state.i.wrapping_add(x as u16 + 1);
     }
-
     Ok(())
 }
 
-pub fn ld_vx_k(state: &mut ChipState, x: u8) -> Result<(), String> {
+pub fn ld_vx_k(state: &mut ChipState, x: u8) -> Result<(), Fault> {
     state.keyboard_halt = KeyboardHalt::Halt(x);
     Ok(())
 }
 
-#[allow(unused_variables)]
-pub fn scd(state: &mut ChipState, n: u8) -> Result<(), String> {
-    Err("Super Chip-48 instruction not implemented".to_string())
+pub fn scd(state: &mut ChipState, n: u8) -> Result<(), Fault> {
+    scroll(state, 0, n as isize);
+    Ok(())
 }
 
-#[allow(unused_variables)]
-pub fn scr(state: &mut ChipState) -> Result<(), String> {
-    Err("Super Chip-48 instruction not implemented".to_string())
+pub fn scr(state: &mut ChipState) -> Result<(), Fault> {
+    scroll(state, 4, 0);
+    Ok(())
 }
 
-#[allow(unused_variables)]
-pub fn scl(state: &mut ChipState) -> Result<(), String> {
-    Err("Super Chip-48 instruction not implemented".to_string())
+pub fn scl(state: &mut ChipState) -> Result<(), Fault> {
+    scroll(state, -4, 0);
+    Ok(())
 }
 
-#[allow(unused_variables)]
-pub fn exit(state: &mut ChipState) -> Result<(), String> {
-    Err("Super Chip-48 instruction not implemented".to_string())
+// Moves the selected planes by (dx, dy) pixels, filling the uncovered area
+// with blank pixels.
+fn scroll(state: &mut ChipState, dx: isize, dy: isize) {
+    let width = state.display_width() as isize;
+    let height = state.display_height() as isize;
+    let previous = state.display;
+    for y in 0..height {
+        for x in 0..width {
+            let (source_x, source_y) = (x - dx, y - dy);
+            let source = if (0..width).contains(&source_x) && (0..height).contains(&source_y) {
+                previous[(source_y * width + source_x) as usize]
+            } else {
+                0
+            };
+            let index = (y * width + x) as usize;
+            state.display[index] = (state.display[index] & !state.plane) | (source & state.plane);
+        }
+    }
+    state.draw_flag = true;
+}
+
+pub fn exit(state: &mut ChipState) -> Result<(), Fault> {
+    state.exit_flag = true;
+    Ok(())
+}
+
+pub fn low(state: &mut ChipState) -> Result<(), Fault> {
+    state.hires = false;
+    state.display = [0; HIRES_WIDTH * HIRES_HEIGHT];
+    state.draw_flag = true;
+    Ok(())
+}
+
+pub fn high(state: &mut ChipState) -> Result<(), Fault> {
+    state.hires = true;
+    state.display = [0; HIRES_WIDTH * HIRES_HEIGHT];
+    state.draw_flag = true;
+    Ok(())
 }
 
-#[allow(unused_variables)]
-pub fn low(state: &mut ChipState) -> Result<(), String> {
-    Err("Super Chip-48 instruction not implemented".to_string())
+pub fn drw_vx_vy_0(
+    state: &mut ChipState,
+    x: u8,
+    y: u8,
+    clipping: bool,
+    display_wait: bool,
+) -> Result<(), Fault> {
+    draw_sprite(state, x, y, 16, 16, clipping)?;
+    state.vblank_wait = display_wait;
+    Ok(())
 }
 
-#[allow(unused_variables)]
-pub fn high(state: &mut ChipState) -> Result<(), String> {
-    Err("Super Chip-48 instruction not implemented".to_string())
+pub fn ld_hf_vx(state: &mut ChipState, x: u8) -> Result<(), Fault> {
+    state.i = BIG_FONT_ADDR as u16 + (state.v[x as usize] & 0xF) as u16 * 10;
+    Ok(())
 }
 
-#[allow(unused_variables)]
-pub fn drw_vx_vy_0(state: &mut ChipState, x: u8, y: u8) -> Result<(), String> {
-    Err("Super Chip-48 instruction not implemented".to_string())
+pub fn ld_r_vx(state: &mut ChipState, x: u8) -> Result<(), Fault> {
+    for i in 0..=x as usize {
+        state.rpl[i] = state.v[i];
+    }
+    Ok(())
 }
 
-#[allow(unused_variables)]
-pub fn ld_hf_vx(state: &mut ChipState, x: u8) -> Result<(), String> {
-    Err("Super Chip-48 instruction not implemented".to_string())
+pub fn ld_vx_r(state: &mut ChipState, x: u8) -> Result<(), Fault> {
+    for i in 0..=x as usize {
+        state.v[i] = state.rpl[i];
+    }
+    Ok(())
 }
 
-#[allow(unused_variables)]
-pub fn ld_r_vx(state: &mut ChipState, x: u8) -> Result<(), String> {
-    Err("Super Chip-48 instruction not implemented".to_string())
+pub fn ld_i_vx_vy(state: &mut ChipState, x: u8, y: u8) -> Result<(), Fault> {
+    for (offset, reg) in register_range(x, y).into_iter().enumerate() {
+        write(state, state.i as usize + offset, state.v[reg])?;
+    }
+    Ok(())
 }
 
-#[allow(unused_variables)]
-pub fn ld_vx_r(state: &mut ChipState, x: u8) -> Result<(), String> {
-    Err("Super Chip-48 instruction not implemented".to_string())
+pub fn ld_vx_vy_i(state: &mut ChipState, x: u8, y: u8) -> Result<(), Fault> {
+    for (offset, reg) in register_range(x, y).into_iter().enumerate() {
+        state.v[reg] = read(state, state.i as usize + offset, AccessKind::Read)?;
+    }
+    Ok(())
+}
+
+// Registers x through y inclusive, in descending order when x > y.
+fn register_range(x: u8, y: u8) -> Vec<usize> {
+    let (x, y) = (x as usize, y as usize);
+    if x <= y {
+        (x..=y).collect()
+    } else {
+        (y..=x).rev().collect()
+    }
+}
+
+pub fn ld_i_long(state: &mut ChipState, addr: u16) -> Result<(), Fault> {
+    state.i = addr;
+    state.pc = state.pc.wrapping_add(2);
+    Ok(())
+}
+
+pub fn plane(state: &mut ChipState, n: u8) -> Result<(), Fault> {
+    state.plane = n & 0x3;
+    Ok(())
+}
+
+pub fn audio(state: &mut ChipState) -> Result<(), Fault> {
+    let mut pattern = [0; 16];
+    for (offset, byte) in pattern.iter_mut().enumerate() {
+        *byte = read(state, state.i as usize + offset, AccessKind::Read)?;
+    }
+    state.audio_pattern = Some(pattern);
+    Ok(())
+}
+
+pub fn pitch_vx(state: &mut ChipState, x: u8) -> Result<(), Fault> {
+    state.pitch = state.v[x as usize];
+    Ok(())
 }
@@ -0,0 +1,90 @@
+use std::collections::VecDeque;
+
+use rand_chacha::ChaCha12Rng;
+
+use super::state::ChipState;
+
+// A full copy of the chip before the instruction with index `instruction`
+// executed. Checkpoints are taken at the start of every frame and before
+// every single step, so replaying forward from one never crosses a timer
+// tick or a keypad change.
+pub(crate) struct Checkpoint {
+    pub instruction: u64,
+    pub frame_start: bool,
+    pub state: ChipState,
+    pub rng: ChaCha12Rng,
+    pub rng_draws: u64,
+}
+
+// Execution history used by the debugger to step backwards. Any earlier
+// instruction is reached by restoring the closest checkpoint before it and
+// re-executing the instructions in between.
+pub(crate) struct History {
+    capacity: usize,
+    checkpoints: VecDeque<Checkpoint>,
+}
+
+impl History {
+    // Keeps roughly `seconds` of history at 60 frames per second.
+    pub fn new(seconds: u32) -> Self {
+        History {
+            capacity: (seconds * 60).max(1) as usize,
+            checkpoints: VecDeque::new(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.checkpoints.clear();
+    }
+
+    // Adds a checkpoint. A frame checkpoint replaces the previous one when no
+    // instruction ran in between, so frames spent waiting cost nothing.
+    pub fn record(&mut self, checkpoint: Checkpoint) {
+        if let Some(last) = self.checkpoints.back() {
+            if last.instruction == checkpoint.instruction && last.frame_start {
+                self.checkpoints.pop_back();
+            }
+        }
+        self.checkpoints.push_back(checkpoint);
+        if self.checkpoints.len() > self.capacity {
+            self.checkpoints.pop_front();
+        }
+    }
+
+    // The newest checkpoint at or before `instruction`.
+    pub fn before(&self, instruction: u64) -> Option<&Checkpoint> {
+        self.checkpoints
+            .iter()
+            .rev()
+            .find(|checkpoint| checkpoint.instruction <= instruction)
+    }
+
+    // Start of the newest frame that ran instructions before `instruction`.
+    pub fn frame_before(&self, instruction: u64) -> Option<u64> {
+        self.checkpoints
+            .iter()
+            .rev()
+            .find(|checkpoint| checkpoint.frame_start && checkpoint.instruction < instruction)
+            .map(|checkpoint| checkpoint.instruction)
+    }
+
+    // Instruction indices of the checkpoints, oldest first.
+    pub fn positions(&self) -> Vec<u64> {
+        self.checkpoints
+            .iter()
+            .map(|checkpoint| checkpoint.instruction)
+            .collect()
+    }
+
+    // Drops the checkpoints after `instruction` once execution has been moved
+    // back there, since running forward again may take a different path.
+    pub fn truncate_after(&mut self, instruction: u64) {
+        while self
+            .checkpoints
+            .back()
+            .is_some_and(|checkpoint| checkpoint.instruction > instruction)
+        {
+            self.checkpoints.pop_back();
+        }
+    }
+}
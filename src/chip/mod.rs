@@ -1,46 +1,531 @@
+pub mod assembler;
+pub mod builder;
+mod busywait;
+pub mod cfg;
+pub mod conformance;
+pub mod coverage;
+pub mod debugger;
+pub mod disasm;
+pub mod error;
+pub mod expr;
+pub mod frontend;
 mod functions;
-mod opcode;
+mod history;
+pub mod opcode;
+pub mod platform;
+pub mod profiler;
+pub mod quirks;
+pub mod rewind;
+pub mod sanitizer;
+pub mod savestate;
+pub mod screenshot;
+pub mod script;
 pub mod state;
+pub mod trace;
 
-use std::fmt::Display;
+use std::{
+    collections::VecDeque,
+    error::Error,
+    fmt::Display,
+    hash::{DefaultHasher, Hash, Hasher},
+    io::Write,
+    ops::Range,
+    path::PathBuf,
+};
 
-use beep::beep;
+use builder::ChipBuilder;
+use busywait::BusyWait;
+use coverage::Coverage;
+use debugger::{DebugCommand, Debugger, WatchHit};
+use error::{ChipError, TrapPolicy};
+use frontend::{Frontend, KeyboardEvent};
+use functions::Fault;
+use history::{Checkpoint, History};
 use opcode::OpCode;
-use state::{ChipState, KeyboardHalt};
+use platform::Platform;
+use profiler::Profiler;
+use quirks::Quirks;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use rewind::RewindBuffer;
+use sanitizer::Sanitizer;
+use savestate::{rom_hash, SaveState};
+use script::InputScript;
+use state::{
+    AccessKind, ChipState, KeyboardHalt, MemoryAccess, BIG_FONT_ADDR, FONT_ADDR, PROGRAM_START,
+};
 
-use crate::terminal::Terminal;
+// Frames `Chip::run_frames` looks back for a repeated state. Idle loops
+// repeat within as many frames as they have instructions.
+const IDLE_FRAMES: usize = 60;
 
-#[derive(Debug)]
-enum ChipError {
-    InvalidOpcode(u16),
+// A frame looked back on by `Chip::run_frames`. Memory is left out of the
+// state and the display and memory are summed up by a hash.
+struct RecentFrame {
+    hash: u64,
+    rng_draws: u64,
+    state: ChipState,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RunResult {
+    Running,           // The chip can keep running
+    Exited,            // The ROM executed the SUPER-CHIP EXIT instruction
+    Quit,              // The frontend asked to stop the emulator
+    Halted(ChipError), // An instruction trapped under `TrapPolicy::Halt`
+    Break(ChipError),  // An instruction trapped under `TrapPolicy::Break`
+    Paused,            // The debugger stopped execution
+    Idle(Idle),        // Nothing will change anymore, only reported by `run_frames`
 }
 
-impl Display for ChipError {
+// Why a ROM stopped making progress.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Idle {
+    SelfJump(u16), // A jump to its own address
+    Loop(u16),     // A loop that leaves everything as it was, ending at the address
+    KeyWait(u16),  // An FX0A key wait without input to come
+}
+
+impl Display for Idle {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        match self {
-            ChipError::InvalidOpcode(opcode) => write!(f, "Invalid opcode: {:#X}", opcode),
+        match *self {
+            Idle::SelfJump(pc) => write!(f, "jump to itself at {:#05X}", pc),
+            Idle::Loop(pc) => write!(f, "loop without effect at {:#05X}", pc),
+            Idle::KeyWait(pc) => write!(f, "waiting for a key that never comes at {:#05X}", pc),
         }
     }
 }
 
-pub struct Chip<'a> {
-    clock_speed: u64, // Clock speed in kHz
+#[derive(Debug)]
+pub struct FrameOutput {
+    pub draw: bool,        // The display changed during the frame
+    pub tone: Option<u16>, // Buzzer frequency, `None` when silent
+    pub result: RunResult,
+}
+
+pub struct Chip {
+    instructions_per_frame: u64,
     state: ChipState,
-    terminal: &'a mut Terminal,
-    shift_quirk: bool,
+    quirks: Quirks,
+    platform: Platform,
+    rom: Vec<u8>,
+    seed: u64,
+    // The algorithm behind StdRng, which can report its stream position
+    rng: ChaCha12Rng,
+    rng_draws: u64,
+    trap_policy: TrapPolicy,
+    trap: Option<ChipError>,
+    save_path: Option<PathBuf>,
+    rewind: Option<RewindBuffer>,
+    history: Option<History>,
+    instructions: u64,
+    trace: Option<Box<dyn Write>>,
+    profiler: Option<Profiler>,
+    coverage: Option<Coverage>,
+    sanitizer: Option<Sanitizer>,
+    random_memory: bool,
+    debugger: Debugger,
+    busy_wait: BusyWait,
 }
 
-impl<'a> Chip<'a> {
-    pub fn new(clock_speed: u64, terminal: &'a mut Terminal, shift_quirk: bool) -> Self {
-        Chip {
-            clock_speed,
-            state: ChipState::new(),
-            terminal,
-            shift_quirk,
+impl Chip {
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        instructions_per_frame: u64,
+        quirks: Quirks,
+        platform: Platform,
+        seed: u64,
+        trap_policy: TrapPolicy,
+        save_path: Option<PathBuf>,
+        rewind: Option<RewindBuffer>,
+        history: Option<History>,
+        trace: Option<Box<dyn Write>>,
+        profiler: Option<Profiler>,
+        coverage: Option<Coverage>,
+        sanitizer: Option<Sanitizer>,
+        random_memory: bool,
+    ) -> Self {
+        let mut chip = Chip {
+            instructions_per_frame,
+            state: ChipState::with_memory_size(platform.memory_size()),
+            quirks,
+            platform,
+            rom: Vec::new(),
+            seed,
+            rng: ChaCha12Rng::seed_from_u64(seed),
+            rng_draws: 0,
+            trap_policy,
+            trap: None,
+            save_path,
+            rewind,
+            history,
+            instructions: 0,
+            trace,
+            profiler,
+            coverage,
+            sanitizer,
+            random_memory,
+            debugger: Debugger::new(),
+            busy_wait: BusyWait::new(),
+        };
+        chip.load_fonts();
+        chip
+    }
+
+    pub fn builder() -> ChipBuilder {
+        ChipBuilder::new()
+    }
+
+    pub fn state(&self) -> &ChipState {
+        &self.state
+    }
+
+    pub fn quirks(&self) -> &Quirks {
+        &self.quirks
+    }
+
+    pub fn platform(&self) -> Platform {
+        self.platform
+    }
+
+    // Seed of the generator used by CXNN. Running the same ROM with the same
+    // seed and input always produces the same random numbers.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    // The error that stopped the chip, if any.
+    pub fn trap(&self) -> Option<ChipError> {
+        self.trap
+    }
+
+    // Clears a trap raised under `TrapPolicy::Break` so the faulting
+    // instruction is retried on the next cycle.
+    fn clear_break_trap(&mut self) {
+        if self.trap_policy == TrapPolicy::Break {
+            self.trap = None;
+        }
+    }
+
+    pub fn debugger(&self) -> &Debugger {
+        &self.debugger
+    }
+
+    pub fn debugger_mut(&mut self) -> &mut Debugger {
+        &mut self.debugger
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.debugger.is_paused()
+    }
+
+    pub fn pause(&mut self) {
+        self.debugger.pause();
+    }
+
+    // Continues after a pause, breakpoint or `TrapPolicy::Break` trap.
+    pub fn resume(&mut self) {
+        self.clear_break_trap();
+        self.debugger.resume();
+    }
+
+    // Executes exactly one instruction and stays paused.
+    pub fn step(&mut self) {
+        self.clear_break_trap();
+        self.debugger.pause();
+        if self.trap.is_some()
+            || self.state.exit_flag
+            || self.state.keyboard_halt != KeyboardHalt::Resume
+        {
+            return;
+        }
+        self.checkpoint(false);
+        self.execute_instruction();
+    }
+
+    // Execution statistics, when profiling was enabled on the builder.
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.profiler.as_ref()
+    }
+
+    // Memory coverage, when enabled on the builder.
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_ref()
+    }
+
+    // The strict mode checks, when enabled on the builder.
+    pub fn sanitizer(&self) -> Option<&Sanitizer> {
+        self.sanitizer.as_ref()
+    }
+
+    // Number of instructions executed since the ROM was loaded.
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    // Undoes the last instruction and pauses. Returns false when the history
+    // is disabled or does not reach back that far.
+    pub fn step_back(&mut self) -> bool {
+        match self.instructions.checked_sub(1) {
+            Some(target) => self.move_back(target, None),
+            None => false,
+        }
+    }
+
+    // Goes back to the start of the newest frame that executed an
+    // instruction and pauses.
+    pub fn step_back_frame(&mut self) -> bool {
+        match self
+            .history
+            .as_ref()
+            .and_then(|history| history.frame_before(self.instructions))
+        {
+            Some(target) => self.move_back(target, None),
+            None => false,
+        }
+    }
+
+    // Goes back to the newest point in the history where a breakpoint fired
+    // or, just before the instruction that made it, a watched access
+    // happened. Nothing changes when there is none.
+    pub fn run_back(&mut self) -> bool {
+        let Some(positions) = self.history.as_ref().map(History::positions) else {
+            return false;
+        };
+        let current = self.instructions;
+        let saved = (
+            self.state.clone(),
+            self.rng.clone(),
+            self.rng_draws,
+            self.trap,
+        );
+        // Replay the segments between checkpoints newest first, keeping the
+        // last hit of the first segment that has any.
+        let mut found = None;
+        for (index, &start) in positions.iter().enumerate().rev() {
+            let end = positions
+                .get(index + 1)
+                .map_or(current, |&end| end.min(current));
+            if start >= end || !self.seek(start) {
+                continue;
+            }
+            while self.instructions < end && self.trap.is_none() {
+                let (position, pc) = (self.instructions, self.state.pc);
+                if self.debugger.breakpoint_hit(&self.state) {
+                    found = Some((position, None));
+                }
+                self.replay_instruction();
+                if let Some(access) = self.debugger.watched_access(&self.state.accesses) {
+                    found = Some((position, Some(WatchHit { pc, access })));
+                }
+            }
+            if found.is_some() {
+                break;
+            }
+        }
+        match found {
+            Some((target, watch_hit)) => self.move_back(target, watch_hit),
+            None => {
+                (self.state, self.rng, self.rng_draws, self.trap) = saved;
+                self.instructions = current;
+                false
+            }
+        }
+    }
+
+    // Moves execution back to before instruction `target` and pauses there.
+    fn move_back(&mut self, target: u64, watch_hit: Option<WatchHit>) -> bool {
+        if !self.seek(target) {
+            return false;
+        }
+        if let Some(history) = self.history.as_mut() {
+            history.truncate_after(target);
+        }
+        self.debugger.pause();
+        self.debugger.set_watch_hit(watch_hit);
+        self.state.draw_flag = true;
+        true
+    }
+
+    // Restores the checkpoint closest to `target` and re-executes up to it.
+    fn seek(&mut self, target: u64) -> bool {
+        let Some(checkpoint) = self
+            .history
+            .as_ref()
+            .and_then(|history| history.before(target))
+        else {
+            return false;
+        };
+        self.state = checkpoint.state.clone();
+        self.rng = checkpoint.rng.clone();
+        self.rng_draws = checkpoint.rng_draws;
+        self.instructions = checkpoint.instruction;
+        self.trap = None;
+        while self.instructions < target && self.trap.is_none() {
+            self.replay_instruction();
+        }
+        true
+    }
+
+    // Executes an instruction like `execute_instruction`, without checking
+    // watchpoints.
+    fn replay_instruction(&mut self) {
+        if let Err(err) = self.cycle() {
+            self.handle_trap(err);
+        }
+        self.instructions += 1;
+    }
+
+    fn checkpoint(&mut self, frame_start: bool) {
+        if let Some(history) = self.history.as_mut() {
+            history.record(Checkpoint {
+                instruction: self.instructions,
+                frame_start,
+                state: self.state.clone(),
+                rng: self.rng.clone(),
+                rng_draws: self.rng_draws,
+            });
+        }
+    }
+
+    // Like `step`, but runs a CALL until it returns to the next instruction.
+    pub fn step_over(&mut self) {
+        if let Ok(OpCode::CALL(_)) = self.decode_at(self.state.pc) {
+            self.clear_break_trap();
+            self.debugger
+                .run_to(self.state.pc.wrapping_add(2), Some(self.state.sp));
+        } else {
+            self.step();
+        }
+    }
+
+    // Runs until the current subroutine returns.
+    pub fn step_out(&mut self) {
+        self.clear_break_trap();
+        self.debugger.run_until_return(self.state.sp);
+    }
+
+    pub fn debug(&mut self, command: DebugCommand) {
+        match command {
+            DebugCommand::Step => self.step(),
+            DebugCommand::StepOver => self.step_over(),
+            DebugCommand::StepOut => self.step_out(),
+            DebugCommand::ToggleBreakpoint(addr) => self.debugger.toggle_breakpoint(addr),
+            DebugCommand::RunTo(addr) => {
+                self.clear_break_trap();
+                self.debugger.run_to(addr, None);
+            }
+            DebugCommand::StepBack => {
+                self.step_back();
+            }
+            DebugCommand::StepBackFrame => {
+                self.step_back_frame();
+            }
+            DebugCommand::RunBack => {
+                self.run_back();
+            }
+        }
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = ChaCha12Rng::seed_from_u64(seed);
+        self.rng_draws = 0;
+    }
+
+    fn random_byte(&mut self) -> u8 {
+        self.rng_draws += 1;
+        self.rng.gen()
+    }
+
+    // The ROM as it was loaded.
+    pub fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    pub fn rom_hash(&self) -> u64 {
+        rom_hash(&self.rom)
+    }
+
+    pub fn save_state(&self) -> SaveState {
+        SaveState {
+            rom_hash: self.rom_hash(),
+            platform: self.platform,
+            quirks: self.quirks,
+            seed: self.seed,
+            rng_draws: self.rng_draws,
+            rng_pos: self.rng.get_word_pos(),
+            state: self.state.clone(),
+        }
+    }
+
+    pub fn load_state(&mut self, save: SaveState) {
+        self.platform = save.platform;
+        self.quirks = save.quirks;
+        self.set_seed(save.seed);
+        self.rng.set_word_pos(save.rng_pos);
+        self.rng_draws = save.rng_draws;
+        self.state = save.state;
+        self.trap = None;
+        if let Some(history) = self.history.as_mut() {
+            history.clear();
+        }
+        // How memory got its contents is unknown, so trust all of it
+        if let Some(sanitizer) = self.sanitizer.as_mut() {
+            let memory_size = self.state.memory.len();
+            sanitizer.reset(memory_size, std::iter::once(0..memory_size));
         }
     }
 
-    fn load_fonts(&mut self) {
+    // Steps back to the previous snapshot of the rewind buffer. Returns false
+    // when rewinding is disabled or there is no history.
+    pub fn rewind(&mut self) -> bool {
+        let rom_hash = self.rom_hash();
+        let Some(save) = self
+            .rewind
+            .as_mut()
+            .and_then(|rewind| rewind.step_back(rom_hash))
+        else {
+            return false;
+        };
+        // Only the machine and the generator move; the seed, platform and
+        // quirks are those the snapshot was taken with
+        match save {
+            Ok(save) => {
+                self.state = save.state;
+                self.rng.set_word_pos(save.rng_pos);
+                self.rng_draws = save.rng_draws;
+                self.trap = None;
+                if let Some(history) = self.history.as_mut() {
+                    history.clear();
+                }
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    fn slot_path(&self, slot: u8) -> Option<PathBuf> {
+        let mut path = self.save_path.clone()?.into_os_string();
+        path.push(format!(".{}.state", slot));
+        Some(path.into())
+    }
+
+    pub fn save_slot(&self, slot: u8) -> Result<PathBuf, Box<dyn Error>> {
+        let path = self.slot_path(slot).ok_or("Save states are disabled")?;
+        std::fs::write(&path, self.save_state().to_bytes())?;
+        Ok(path)
+    }
+
+    pub fn load_slot(&mut self, slot: u8) -> Result<PathBuf, Box<dyn Error>> {
+        let path = self.slot_path(slot).ok_or("Save states are disabled")?;
+        let data = std::fs::read(&path)?;
+        self.load_state(SaveState::from_bytes(&data, self.rom_hash())?);
+        Ok(path)
+    }
+
+    // Copies both fonts to memory, returning the range they occupy.
+    fn load_fonts(&mut self) -> Range<usize> {
         let fonts = [
             0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
             0x20, 0x60, 0x20, 0x20, 0x70, // 1
@@ -60,18 +545,71 @@ impl<'a> Chip<'a> {
             0xF0, 0x80, 0xF0, 0x80, 0x80, // F
         ];
 
+        let big_fonts = [
+            0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C, // 0
+            0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C, // 1
+            0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF, // 2
+            0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C, // 3
+            0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06, // 4
+            0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C, // 5
+            0x3E, 0x7C, 0xC0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C, // 6
+            0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x60, 0x60, // 7
+            0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C, // 8
+            0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C, // 9
+            0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+            0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+            0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+            0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+            0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+            0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F
+        ];
+
         for (i, &font) in fonts.iter().enumerate() {
-            self.state.memory[i] = font;
+            self.state.memory[FONT_ADDR + i] = font;
+        }
+        for (i, &font) in big_fonts.iter().enumerate() {
+            self.state.memory[BIG_FONT_ADDR + i] = font;
         }
+        FONT_ADDR..BIG_FONT_ADDR + big_fonts.len()
     }
 
+    // Restarts the loaded ROM from a clean state.
     pub fn reset(&mut self) {
-        self.state = ChipState::new();
+        self.state = ChipState::with_memory_size(self.platform.memory_size());
+        self.set_seed(self.seed);
+        self.trap = None;
+        if let Some(rewind) = self.rewind.as_mut() {
+            rewind.clear();
+        }
+        if let Some(history) = self.history.as_mut() {
+            history.clear();
+        }
+        self.instructions = 0;
+        // Garbage from a generator of its own, with a seed of its own so it
+        // does not repeat the CXNN stream
+        if self.random_memory {
+            StdRng::seed_from_u64(self.seed ^ 0x9E37_79B9_7F4A_7C15)
+                .fill(&mut self.state.memory[..]);
+        }
+        self.state.load_rom(self.rom.clone());
+        let fonts = self.load_fonts();
+        if let Some(sanitizer) = self.sanitizer.as_mut() {
+            let rom = PROGRAM_START..PROGRAM_START + self.rom.len();
+            sanitizer.reset(self.state.memory.len(), [fonts, rom]);
+        }
     }
 
-    pub fn load_rom(&mut self, rom: Vec<u8>) {
-        self.state.load_rom(rom);
-        self.load_fonts();
+    pub fn load_rom(&mut self, rom: Vec<u8>) -> Result<(), ChipError> {
+        let max = self.platform.memory_size() - PROGRAM_START;
+        if rom.len() > max {
+            return Err(ChipError::RomTooLarge {
+                size: rom.len(),
+                max,
+            });
+        }
+        self.rom = rom;
+        self.reset();
+        Ok(())
     }
 
     pub fn set_key(&mut self, new_state: [bool; 16]) {
@@ -79,130 +617,27 @@ impl<'a> Chip<'a> {
     }
 
     fn fetch_opcode(&self) -> u16 {
-        let pc = self.state.pc as usize;
-        let byte1 = self.state.memory[pc] as u16;
-        let byte2 = self.state.memory[pc + 1] as u16;
+        self.fetch_word(self.state.pc)
+    }
+
+    fn fetch_word(&self, addr: u16) -> u16 {
+        let addr = addr as usize % self.state.memory.len();
+        let byte1 = self.state.memory[addr] as u16;
+        let byte2 = self.state.memory[(addr + 1) % self.state.memory.len()] as u16;
 
         (byte1 << 8) | byte2
     }
 
-    fn decode_opcode(&self, opcode: u16) -> Result<OpCode, ChipError> {
-        use OpCode::*;
+    // Decodes the instruction at `addr` without executing it.
+    pub fn decode_at(&self, addr: u16) -> Result<OpCode, ChipError> {
+        self.decode_opcode(addr, self.fetch_word(addr))
+    }
 
-        match opcode {
-            0x0000..=0x00FF => match opcode & 0x00FF {
-                0x00E0 => Ok(CLS),
-                0x00EE => Ok(RET),
-                0x00C0..=0x00CF => Ok(SCD((opcode & 0x000F) as u8)),
-                0x00FB => Ok(SCR),
-                0x00FC => Ok(SCL),
-                0x00FD => Ok(EXIT),
-                0x00FE => Ok(LOW),
-                0x00FF => Ok(HIGH),
-                _ => Ok(SYSADDR(opcode & 0x0FFF)),
-            },
-            0x1000..=0x1FFF => Ok(JP(opcode & 0x0FFF)),
-            0x2000..=0x2FFF => Ok(CALL(opcode & 0x0FFF)),
-            0x3000..=0x3FFF => Ok(SEVxByte(
-                ((opcode & 0x0F00) >> 8) as u8,
-                (opcode & 0x00FF) as u8,
-            )),
-            0x4000..=0x4FFF => Ok(SNEVxByte(
-                ((opcode & 0x0F00) >> 8) as u8,
-                (opcode & 0x00FF) as u8,
-            )),
-            0x5000..=0x5FFF => Ok(SEVxVy(
-                ((opcode & 0x0F00) >> 8) as u8,
-                ((opcode & 0x00F0) >> 4) as u8,
-            )),
-            0x6000..=0x6FFF => Ok(LDVxByte(
-                ((opcode & 0x0F00) >> 8) as u8,
-                (opcode & 0x00FF) as u8,
-            )),
-            0x7000..=0x7FFF => Ok(ADDVxByte(
-                ((opcode & 0x0F00) >> 8) as u8,
-                (opcode & 0x00FF) as u8,
-            )),
-            0x8000..=0x8FFF => match opcode & 0x000F {
-                0x0 => Ok(LDVxVy(
-                    ((opcode & 0x0F00) >> 8) as u8,
-                    ((opcode & 0x00F0) >> 4) as u8,
-                )),
-                0x1 => Ok(ORVxVy(
-                    ((opcode & 0x0F00) >> 8) as u8,
-                    ((opcode & 0x00F0) >> 4) as u8,
-                )),
-                0x2 => Ok(ANDVxVy(
-                    ((opcode & 0x0F00) >> 8) as u8,
-                    ((opcode & 0x00F0) >> 4) as u8,
-                )),
-                0x3 => Ok(XORVxVy(
-                    ((opcode & 0x0F00) >> 8) as u8,
-                    ((opcode & 0x00F0) >> 4) as u8,
-                )),
-                0x4 => Ok(ADDVxVy(
-                    ((opcode & 0x0F00) >> 8) as u8,
-                    ((opcode & 0x00F0) >> 4) as u8,
-                )),
-                0x5 => Ok(SUBVxVy(
-                    ((opcode & 0x0F00) >> 8) as u8,
-                    ((opcode & 0x00F0) >> 4) as u8,
-                )),
-                0x6 => Ok(SHRVyVx(
-                    ((opcode & 0x0F00) >> 8) as u8,
-                    ((opcode & 0x00F0) >> 4) as u8,
-                )),
-                0x7 => Ok(SUBNVyVx(
-                    ((opcode & 0x0F00) >> 8) as u8,
-                    ((opcode & 0x00F0) >> 4) as u8,
-                )),
-                0xE => Ok(SHLVyVx(
-                    ((opcode & 0x0F00) >> 8) as u8,
-                    ((opcode & 0x00F0) >> 4) as u8,
-                )),
-                _ => Err(ChipError::InvalidOpcode(opcode)),
-            },
-            0x9000..=0x9FFF => Ok(SNEVxVy(
-                ((opcode & 0x0F00) >> 8) as u8,
-                ((opcode & 0x00F0) >> 4) as u8,
-            )),
-            0xA000..=0xAFFF => Ok(LDI(opcode & 0x0FFF)),
-            0xB000..=0xBFFF => Ok(JP0(opcode & 0x0FFF)),
-            0xC000..=0xCFFF => Ok(RND(((opcode & 0x0F00) >> 8) as u8, (opcode & 0x00FF) as u8)),
-            0xD000..=0xDFFF => match opcode & 0x000F {
-                0x0 => Ok(DRWVxVy0(
-                    ((opcode & 0x0F00) >> 8) as u8,
-                    ((opcode & 0x00F0) >> 4) as u8,
-                )),
-                _ => Ok(DRW(
-                    ((opcode & 0x0F00) >> 8) as u8,
-                    ((opcode & 0x00F0) >> 4) as u8,
-                    (opcode & 0x000F) as u8,
-                )),
-            },
-            0xE000..=0xEFFF => match opcode & 0x00FF {
-                0x9E => Ok(SKPVx(((opcode & 0x0F00) >> 8) as u8)),
-                0xA1 => Ok(SKNPVx(((opcode & 0x0F00) >> 8) as u8)),
-                _ => Err(ChipError::InvalidOpcode(opcode)),
-            },
-
-            0xF000..=0xFFFF => match opcode & 0x00FF {
-                0x07 => Ok(LDVxDT(((opcode & 0x0F00) >> 8) as u8)),
-                0x0A => Ok(LDVxK(((opcode & 0x0F00) >> 8) as u8)),
-                0x15 => Ok(LDDTVx(((opcode & 0x0F00) >> 8) as u8)),
-                0x18 => Ok(LDSTVx(((opcode & 0x0F00) >> 8) as u8)),
-                0x1E => Ok(ADDIVx(((opcode & 0x0F00) >> 8) as u8)),
-                0x29 => Ok(LDFVx(((opcode & 0x0F00) >> 8) as u8)),
-                0x33 => Ok(LDBVx(((opcode & 0x0F00) >> 8) as u8)),
-                0x55 => Ok(LDIVx(((opcode & 0x0F00) >> 8) as u8)),
-                0x65 => Ok(LDVxI(((opcode & 0x0F00) >> 8) as u8)),
-                _ => Err(ChipError::InvalidOpcode(opcode)),
-            },
-            _ => Err(ChipError::InvalidOpcode(opcode)),
-        }
-    }
-
-    fn execute_opcode(&mut self, opcode: OpCode) -> Result<(), String> {
+    fn decode_opcode(&self, pc: u16, opcode: u16) -> Result<OpCode, ChipError> {
+        OpCode::decode(pc, opcode, self.fetch_word(pc.wrapping_add(2)))
+    }
+
+    fn execute_opcode(&mut self, opcode: OpCode) -> Result<(), Fault> {
         use OpCode::*;
 
         match opcode {
@@ -217,19 +652,29 @@ impl<'a> Chip<'a> {
             LDVxByte(x, byte) => functions::ld_vx_byte(&mut self.state, x, byte),
             ADDVxByte(x, byte) => functions::add_vx_byte(&mut self.state, x, byte),
             LDVxVy(x, y) => functions::ld_vx_vy(&mut self.state, x, y),
-            ORVxVy(x, y) => functions::or_vx_vy(&mut self.state, x, y),
-            ANDVxVy(x, y) => functions::and_vx_vy(&mut self.state, x, y),
-            XORVxVy(x, y) => functions::xor_vx_vy(&mut self.state, x, y),
+            ORVxVy(x, y) => functions::or_vx_vy(&mut self.state, x, y, self.quirks.vf_reset),
+            ANDVxVy(x, y) => functions::and_vx_vy(&mut self.state, x, y, self.quirks.vf_reset),
+            XORVxVy(x, y) => functions::xor_vx_vy(&mut self.state, x, y, self.quirks.vf_reset),
             ADDVxVy(x, y) => functions::add_vx_vy(&mut self.state, x, y),
             SUBVxVy(x, y) => functions::sub_vx_vy(&mut self.state, x, y),
-            SHRVyVx(x, y) => functions::shr(&mut self.state, x, y, self.shift_quirk),
+            SHRVyVx(x, y) => functions::shr(&mut self.state, x, y, self.quirks.shifting),
             SUBNVyVx(x, y) => functions::subn_vy_vx(&mut self.state, x, y),
-            SHLVyVx(x, y) => functions::shl(&mut self.state, x, y, self.shift_quirk),
+            SHLVyVx(x, y) => functions::shl(&mut self.state, x, y, self.quirks.shifting),
             SNEVxVy(x, y) => functions::sne_vx_vy(&mut self.state, x, y),
             LDI(addr) => functions::ld_i(&mut self.state, addr),
-            JP0(addr) => functions::jp0(&mut self.state, addr),
-            RND(x, byte) => functions::rnd(&mut self.state, x, byte),
-            DRW(x, y, n) => functions::drw(&mut self.state, x, y, n),
+            JP0(addr) => functions::jp0(&mut self.state, addr, self.quirks.jumping),
+            RND(x, byte) => {
+                let random = self.random_byte();
+                functions::rnd(&mut self.state, x, byte, random)
+            }
+            DRW(x, y, n) => functions::drw(
+                &mut self.state,
+                x,
+                y,
+                n,
+                self.quirks.clipping,
+                self.quirks.display_wait,
+            ),
             SKPVx(x) => functions::skp_vx(&mut self.state, x),
             SKNPVx(x) => functions::sknp_vx(&mut self.state, x),
             LDDTVx(x) => functions::ld_dt_vx(&mut self.state, x),
@@ -238,124 +683,392 @@ impl<'a> Chip<'a> {
             ADDIVx(x) => functions::add_i_vx(&mut self.state, x),
             LDFVx(x) => functions::ld_f_vx(&mut self.state, x),
             LDBVx(x) => functions::ld_b_vx(&mut self.state, x),
-            LDIVx(x) => functions::ld_i_vx(&mut self.state, x),
-            LDVxI(x) => functions::ld_vx_i(&mut self.state, x),
+            LDIVx(x) => functions::ld_i_vx(&mut self.state, x, self.quirks.memory_increment),
+            LDVxI(x) => functions::ld_vx_i(&mut self.state, x, self.quirks.memory_increment),
             LDRVx(x) => functions::ld_r_vx(&mut self.state, x),
             LDVxR(x) => functions::ld_vx_r(&mut self.state, x),
             LDVxK(x) => functions::ld_vx_k(&mut self.state, x),
-            _ => panic!("Unknown opcode, {:?}", opcode),
+            SCD(n) => functions::scd(&mut self.state, n),
+            SCR => functions::scr(&mut self.state),
+            SCL => functions::scl(&mut self.state),
+            EXIT => functions::exit(&mut self.state),
+            LOW => functions::low(&mut self.state),
+            HIGH => functions::high(&mut self.state),
+            DRWVxVy0(x, y) => functions::drw_vx_vy_0(
+                &mut self.state,
+                x,
+                y,
+                self.quirks.clipping,
+                self.quirks.display_wait,
+            ),
+            LDHFVx(x) => functions::ld_hf_vx(&mut self.state, x),
+            LDIVxVy(x, y) => functions::ld_i_vx_vy(&mut self.state, x, y),
+            LDVxVyI(x, y) => functions::ld_vx_vy_i(&mut self.state, x, y),
+            LDILong(addr) => functions::ld_i_long(&mut self.state, addr),
+            PLANE(n) => functions::plane(&mut self.state, n),
+            AUDIO => functions::audio(&mut self.state),
+            PITCHVx(x) => functions::pitch_vx(&mut self.state, x),
         }
     }
 
-    fn update_timers(&mut self) {
-        let elapsed = self.state.last_timer_update.elapsed().unwrap().as_nanos();
-        if elapsed >= 16666666 {
-            if self.state.delay_timer > 0 {
-                self.state.delay_timer -= 1;
-            }
-            if self.state.sound_timer > 0 {
-                self.state.sound_timer -= 1;
-            }
-            self.state.last_timer_update = std::time::SystemTime::now();
+    fn tick_timers(&mut self) {
+        if self.state.delay_timer > 0 {
+            self.state.delay_timer -= 1;
         }
+        if self.state.sound_timer > 0 {
+            self.state.sound_timer -= 1;
+        }
+        self.state.vblank_wait = false;
     }
 
-    fn can_cycle(&self) -> bool {
-        if self.state.keyboard_halt != KeyboardHalt::Resume {
-            return false;
+    fn tone(&self) -> Option<u16> {
+        if self.state.sound_timer > 0 {
+            Some(self.state.audio_frequency().unwrap_or(50000))
+        } else {
+            None
         }
-        let now = std::time::SystemTime::now();
-        let elapsed = now
-            .duration_since(self.state.last_cycle)
-            .unwrap()
-            .as_secs_f32();
-        elapsed >= 1.0 / self.clock_speed as f32
     }
 
-    fn draw(&mut self) {
-        self.terminal.draw(&self.state).unwrap();
+    fn update_keyboard_halt(&mut self) {
+        match self.state.keyboard_halt {
+            KeyboardHalt::Halt(x) if self.state.keypad.iter().any(|&key| key) => {
+                self.state.v[x as usize] =
+                    self.state.keypad.iter().position(|&key| key).unwrap() as u8;
+                self.state.keyboard_halt = KeyboardHalt::WaitForRelease(x);
+            }
+            KeyboardHalt::WaitForRelease(x)
+                if !self.state.keypad[self.state.v[x as usize] as usize] =>
+            {
+                self.state.keyboard_halt = KeyboardHalt::Resume;
+            }
+            _ => {}
+        }
     }
 
-    pub fn cycle(&mut self) {
+    // Executes a single instruction. On error the program counter is left on
+    // the faulting instruction.
+    pub fn cycle(&mut self) -> Result<(), ChipError> {
+        let pc = self.state.pc;
+        self.state.accesses.clear();
+
         // Fetch opcode
         let opcode = self.fetch_opcode();
 
         // Decode opcode
-        let decoded = self.decode_opcode(opcode);
+        let decoded = self.decode_opcode(pc, opcode)?;
+        let length = if let OpCode::LDILong(_) = decoded {
+            4
+        } else {
+            2
+        };
+        self.log_fetch(pc, length);
+        if !self.platform.supports(&decoded) {
+            return Err(ChipError::UnsupportedInstruction { pc, opcode });
+        }
+
+        // Execute opcode
+        self.execute_opcode(decoded)
+            .map_err(|fault| fault.at(pc, opcode))?;
 
-        if let Err(err) = decoded {
-            panic!("{}", err);
+        self.advance_pc();
+        Ok(())
+    }
+
+    fn log_fetch(&mut self, pc: u16, length: usize) {
+        for offset in 0..length {
+            let addr = (pc as usize + offset) % self.state.memory.len();
+            let value = self.state.memory[addr];
+            self.state.accesses.push(MemoryAccess {
+                kind: AccessKind::Fetch,
+                addr: addr as u16,
+                old: value,
+                new: value,
+            });
         }
+    }
 
-        let decoded = decoded.unwrap();
+    // Runs one instruction, applying the trap policy and watchpoints.
+    fn execute_instruction(&mut self) {
+        let (pc, sp, i) = (self.state.pc, self.state.sp, self.state.i);
+        self.trace_instruction();
+        let opcode = if self.profiler.is_some() || self.sanitizer.is_some() {
+            self.decode_at(pc).ok()
+        } else {
+            None
+        };
+        let result = self.cycle();
+        self.instructions += 1;
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.record(pc, opcode, sp, self.state.sp, self.state.pc);
+        }
+        if let Some(coverage) = self.coverage.as_mut() {
+            coverage.record(&self.state.accesses);
+        }
+        if let Some(sanitizer) = self.sanitizer.as_mut() {
+            sanitizer.record(pc, opcode, i, sp, &self.state);
+        }
+        self.debugger.check_watchpoints(pc, &self.state.accesses);
+        if let Err(err) = result {
+            self.handle_trap(err);
+        }
+    }
 
-        // Execute opcode
-        let _ = self
-            .execute_opcode(decoded)
-            .map_err(|err| eprintln!("Error executing opcode: {}", err));
+    // Logs the instruction about to execute. Tracing stops if the log can no
+    // longer be written.
+    fn trace_instruction(&mut self) {
+        let Some(mut out) = self.trace.take() else {
+            return;
+        };
+        let opcode = self.fetch_opcode();
+        let decoded = self.decode_opcode(self.state.pc, opcode);
+        if trace::write_line(&mut out, self.instructions, &self.state, opcode, decoded).is_ok() {
+            self.trace = Some(out);
+        }
+    }
 
+    fn advance_pc(&mut self) {
         // Increment program counter
         if !self.state.jump_flag {
-            self.state.pc += 2;
+            self.state.pc = self.state.pc.wrapping_add(2);
         }
         // Wrap pc around if it goes out of bounds
-        if self.state.pc >= 4096 {
-            self.state.pc -= 4096;
-        }
+        self.state.pc = (self.state.pc as usize % self.state.memory.len()) as u16;
         // Reset jump flag
         self.state.jump_flag = false;
     }
 
-    pub fn run(&mut self) -> String {
-        if self.can_cycle() {
-            self.cycle();
-            self.state.last_cycle = std::time::SystemTime::now();
+    // Applies the trap policy to an error raised by `cycle`.
+    fn handle_trap(&mut self, err: ChipError) {
+        match self.trap_policy {
+            TrapPolicy::Ignore => {
+                self.state.jump_flag = false;
+                self.state.pc = err.pc();
+                self.advance_pc();
+            }
+            TrapPolicy::Halt => self.trap = Some(err),
+            TrapPolicy::Break => {
+                self.trap = Some(err);
+                self.debugger.pause();
+            }
+        }
+    }
+
+    // Polls the frontend for input, runs one frame and presents its output.
+    // Pacing to wall-clock time is left to the caller so headless hosts can
+    // run as fast as possible with identical results. Errors of the frontend
+    // are passed on, the chip is left as it was after the frame.
+    pub fn run(&mut self, frontend: &mut dyn Frontend) -> Result<RunResult, Box<dyn Error>> {
+        match frontend.get_key()? {
+            KeyboardEvent::State(state) => self.set_key(state),
+            KeyboardEvent::Exit => return Ok(RunResult::Quit),
+            KeyboardEvent::Reset => self.reset(),
+            KeyboardEvent::Pause if self.is_paused() => self.resume(),
+            KeyboardEvent::Pause => self.pause(),
+            KeyboardEvent::Debug(command) => self.debug(command),
+            KeyboardEvent::SaveState(slot) => {
+                let message = match self.save_slot(slot) {
+                    Ok(path) => format!("Saved slot {} to {}", slot, path.display()),
+                    Err(e) => format!("Failed to save slot {}: {}", slot, e),
+                };
+                frontend.show_message(&message)?;
+            }
+            KeyboardEvent::Rewind => {
+                self.rewind();
+                frontend.draw(&self.state)?;
+                frontend.set_tone(None);
+                frontend.draw_status(&self.state)?;
+                return Ok(RunResult::Running);
+            }
+            KeyboardEvent::LoadState(slot) => {
+                let message = match self.load_slot(slot) {
+                    Ok(path) => format!("Loaded slot {} from {}", slot, path.display()),
+                    Err(e) => format!("Failed to load slot {}: {}", slot, e),
+                };
+                frontend.show_message(&message)?;
+            }
         }
-        if self.state.draw_flag {
-            self.draw();
+
+        let output = self.step_frame(self.state.keypad);
+        if output.draw {
+            frontend.draw(&self.state)?;
+        }
+        frontend.set_tone(output.tone);
+        frontend.draw_status(&self.state)?;
+        frontend.draw_debugger(self)?;
+
+        Ok(output.result)
+    }
+
+    // Runs one 60 Hz frame worth of instructions with the given keypad state
+    // and ticks the timers exactly once, without any wall-clock pacing. The
+    // frame ends early when the ROM exits, waits for a key, hits a breakpoint
+    // or, with the display wait quirk, draws a sprite. Nothing runs and the
+    // timers stand still while paused or trapped.
+    pub fn step_frame(&mut self, keypad: [bool; 16]) -> FrameOutput {
+        self.set_key(keypad);
+        self.update_keyboard_halt();
+
+        if self.trap.is_some() || self.debugger.is_paused() {
+            let output = FrameOutput {
+                draw: self.state.draw_flag,
+                tone: None,
+                result: self.result(),
+            };
             self.state.draw_flag = false;
+            return output;
         }
-        match self.terminal.get_key() {
-            Ok(event) => match event {
-                crate::terminal::KeyboardEvent::State(state) => self.set_key(state),
-                crate::terminal::KeyboardEvent::Exit => return String::from("exit"),
-                crate::terminal::KeyboardEvent::Reset => self.reset(),
-                crate::terminal::KeyboardEvent::Pause => {
-                    std::thread::sleep(std::time::Duration::from_secs(1));
-                }
-            },
-            Err(e) => eprintln!("Error getting key: {}", e),
+
+        self.checkpoint(true);
+        self.busy_wait.reset();
+        let fast_forward = self.can_fast_forward();
+        let mut remaining = self.instructions_per_frame;
+        while remaining > 0 {
+            if self.state.exit_flag
+                || self.state.vblank_wait
+                || self.state.keyboard_halt != KeyboardHalt::Resume
+                || self.trap.is_some()
+                || self.debugger.should_break(&self.state)
+            {
+                break;
+            }
+            let pc = self.state.pc;
+            let opcode = if fast_forward {
+                self.decode_at(pc).ok()
+            } else {
+                None
+            };
+            self.execute_instruction();
+            remaining -= 1;
+            if fast_forward {
+                // Loops polling the delay timer or keys spin until the frame
+                // ends, skip their remaining iterations
+                let skipped = self.busy_wait.record(pc, opcode, &self.state, remaining);
+                self.instructions += skipped;
+                remaining -= skipped;
+            }
+        }
+        self.tick_timers();
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.end_frame(self.state.keyboard_halt != KeyboardHalt::Resume);
         }
 
-        // Wait for key press
-        match self.state.keyboard_halt {
-            state::KeyboardHalt::Halt(x) => {
-                if self.state.keypad.iter().any(|&key| key) {
-                    self.state.v[x as usize] =
-                        self.state.keypad.iter().position(|&key| key).unwrap() as u8;
-                    self.state.keyboard_halt = state::KeyboardHalt::WaitForRelease(x);
-                }
+        let output = FrameOutput {
+            draw: self.state.draw_flag,
+            tone: self.tone(),
+            result: self.result(),
+        };
+        if self.rewind.is_some() {
+            let save = self.save_state();
+            if let Some(rewind) = self.rewind.as_mut() {
+                rewind.record(&save);
             }
-            state::KeyboardHalt::WaitForRelease(x) => {
-                if !self.state.keypad[self.state.v[x as usize] as usize] {
-                    self.state.keyboard_halt = state::KeyboardHalt::Resume;
-                }
+        }
+        self.state.draw_flag = false;
+        output
+    }
+
+    // Whether busy waits can be skipped, which is when nothing observes the
+    // individual instructions.
+    fn can_fast_forward(&self) -> bool {
+        self.trace.is_none()
+            && self.profiler.is_none()
+            && self.coverage.is_none()
+            && self.sanitizer.is_none()
+            && !self.debugger.is_armed()
+    }
+
+    // Runs up to `frames` frames back to back, forever without a limit,
+    // with keys from `script`. Stops early when the ROM exits, traps or is
+    // paused, or once it is idle: once the keys will not change again, the
+    // machine came back to the state of a recent frame without anything
+    // visible happening in between, so it would go round the same cycle
+    // forever. Returns the last result and the number of frames that ran.
+    pub fn run_frames(&mut self, frames: Option<u64>, script: &InputScript) -> (RunResult, u64) {
+        let mut recent = VecDeque::new();
+        // The memory of every recent frame, which they all share
+        let mut memory = Vec::new();
+        let mut frame = 0;
+        while frames.is_none_or(|frames| frame < frames) {
+            let output = self.step_frame(script.keys_at(frame));
+            frame += 1;
+            if output.result != RunResult::Running {
+                return (output.result, frame);
             }
-            _ => {}
+            if script.changes_after(frame - 1) {
+                continue;
+            }
+            // Memory is only compared in full when the hash cannot tell it
+            // changed
+            let hash = self.contents_hash();
+            let same_memory = recent
+                .back()
+                .is_some_and(|last: &RecentFrame| last.hash == hash)
+                && self.state.memory == memory;
+            if !same_memory {
+                recent.clear();
+                memory.clone_from(&self.state.memory);
+            } else if self.repeats(&recent, hash) {
+                return (RunResult::Idle(self.idle_reason()), frame);
+            }
+            if recent.len() == IDLE_FRAMES {
+                recent.pop_front();
+            }
+            let memory = std::mem::take(&mut self.state.memory);
+            recent.push_back(RecentFrame {
+                hash,
+                rng_draws: self.rng_draws,
+                state: self.state.clone(),
+            });
+            self.state.memory = memory;
         }
+        (RunResult::Running, frame)
+    }
 
-        // Update timers
-        self.update_timers();
+    // Hash of the display and memory.
+    fn contents_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.state.display.hash(&mut hasher);
+        self.state.memory.hash(&mut hasher);
+        hasher.finish()
+    }
 
-        if self.state.sound_timer > 0 {
-            let _ = beep(50000);
-        } else {
-            let _ = beep(0);
+    // Whether the state matches one of the `recent` frames, newest last,
+    // with the same display, keys and random numbers ever since. They all
+    // have the memory of the current frame.
+    fn repeats(&self, recent: &VecDeque<RecentFrame>, hash: u64) -> bool {
+        for frame in recent.iter().rev() {
+            if frame.hash != hash
+                || frame.rng_draws != self.rng_draws
+                || frame.state.keypad != self.state.keypad
+                || frame.state.display != self.state.display
+            {
+                return false;
+            }
+            if frame.state.same_registers(&self.state) {
+                return true;
+            }
         }
+        false
+    }
 
-        self.terminal.draw_key_state(&self.state.keypad).unwrap();
-        self.terminal.draw_timers(&self.state).unwrap();
+    fn idle_reason(&self) -> Idle {
+        let pc = self.state.pc;
+        if self.state.keyboard_halt != KeyboardHalt::Resume {
+            Idle::KeyWait(pc)
+        } else if self.decode_at(pc) == Ok(OpCode::JP(pc)) {
+            Idle::SelfJump(pc)
+        } else {
+            Idle::Loop(pc)
+        }
+    }
 
-        String::from("Running")
+    fn result(&self) -> RunResult {
+        match self.trap {
+            Some(err) if self.trap_policy == TrapPolicy::Break => RunResult::Break(err),
+            Some(err) => RunResult::Halted(err),
+            None if self.state.exit_flag => RunResult::Exited,
+            None if self.debugger.is_paused() => RunResult::Paused,
+            None => RunResult::Running,
+        }
     }
 }
@@ -1,4 +1,9 @@
-#[derive(Debug, PartialEq)]
+use std::fmt::Display;
+
+use super::error::ChipError;
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum OpCode {
     CLS,               // Clear the display
     RET,               // Return from a subroutine
@@ -45,4 +50,276 @@ pub enum OpCode {
     LDHFVx(u8),       // Set I = location of sprite for digit Vx
     LDRVx(u8),        // Store registers V0 through Vx in memory starting at location I
     LDVxR(u8),        // Read registers V0 through Vx from memory starting at location I
+    LDIVxVy(u8, u8),  // Store registers Vx through Vy in memory starting at location I
+    LDVxVyI(u8, u8),  // Read registers Vx through Vy from memory starting at location I
+    LDILong(u16),     // Set I = nnnn, read from the word following the instruction
+    PLANE(u8),        // Select the drawing planes n
+    AUDIO,            // Load the 16-byte audio pattern buffer from memory starting at location I
+    PITCHVx(u8),      // Set the audio pitch register = Vx
+}
+
+impl OpCode {
+    // Encodes the instruction, the inverse of `decode`. Only the long load
+    // takes four bytes.
+    pub fn encode(&self) -> Vec<u8> {
+        use OpCode::*;
+
+        let xy = |high: u16, x: u8, y: u8, low: u16| high | (x as u16) << 8 | (y as u16) << 4 | low;
+        let xkk = |high: u16, x: u8, byte: u8| high | (x as u16) << 8 | byte as u16;
+        let word = match *self {
+            CLS => 0x00E0,
+            RET => 0x00EE,
+            SYSADDR(addr) => addr & 0x0FFF,
+            JP(addr) => 0x1000 | (addr & 0x0FFF),
+            CALL(addr) => 0x2000 | (addr & 0x0FFF),
+            SEVxByte(x, byte) => xkk(0x3000, x, byte),
+            SNEVxByte(x, byte) => xkk(0x4000, x, byte),
+            SEVxVy(x, y) => xy(0x5000, x, y, 0x0),
+            LDVxByte(x, byte) => xkk(0x6000, x, byte),
+            ADDVxByte(x, byte) => xkk(0x7000, x, byte),
+            LDVxVy(x, y) => xy(0x8000, x, y, 0x0),
+            ORVxVy(x, y) => xy(0x8000, x, y, 0x1),
+            ANDVxVy(x, y) => xy(0x8000, x, y, 0x2),
+            XORVxVy(x, y) => xy(0x8000, x, y, 0x3),
+            ADDVxVy(x, y) => xy(0x8000, x, y, 0x4),
+            SUBVxVy(x, y) => xy(0x8000, x, y, 0x5),
+            SHRVyVx(x, y) => xy(0x8000, x, y, 0x6),
+            SUBNVyVx(x, y) => xy(0x8000, x, y, 0x7),
+            SHLVyVx(x, y) => xy(0x8000, x, y, 0xE),
+            SNEVxVy(x, y) => xy(0x9000, x, y, 0x0),
+            LDI(addr) => 0xA000 | (addr & 0x0FFF),
+            JP0(addr) => 0xB000 | (addr & 0x0FFF),
+            RND(x, byte) => xkk(0xC000, x, byte),
+            DRW(x, y, n) => xy(0xD000, x, y, n as u16 & 0xF),
+            SKPVx(x) => xkk(0xE000, x, 0x9E),
+            SKNPVx(x) => xkk(0xE000, x, 0xA1),
+            LDVxDT(x) => xkk(0xF000, x, 0x07),
+            LDVxK(x) => xkk(0xF000, x, 0x0A),
+            LDDTVx(x) => xkk(0xF000, x, 0x15),
+            LDSTVx(x) => xkk(0xF000, x, 0x18),
+            ADDIVx(x) => xkk(0xF000, x, 0x1E),
+            LDFVx(x) => xkk(0xF000, x, 0x29),
+            LDBVx(x) => xkk(0xF000, x, 0x33),
+            LDIVx(x) => xkk(0xF000, x, 0x55),
+            LDVxI(x) => xkk(0xF000, x, 0x65),
+            SCD(n) => 0x00C0 | (n as u16 & 0xF),
+            SCR => 0x00FB,
+            SCL => 0x00FC,
+            EXIT => 0x00FD,
+            LOW => 0x00FE,
+            HIGH => 0x00FF,
+            DRWVxVy0(x, y) => xy(0xD000, x, y, 0x0),
+            LDHFVx(x) => xkk(0xF000, x, 0x30),
+            LDRVx(x) => xkk(0xF000, x, 0x75),
+            LDVxR(x) => xkk(0xF000, x, 0x85),
+            LDIVxVy(x, y) => xy(0x5000, x, y, 0x2),
+            LDVxVyI(x, y) => xy(0x5000, x, y, 0x3),
+            LDILong(addr) => {
+                let [high, low] = addr.to_be_bytes();
+                return vec![0xF0, 0x00, high, low];
+            }
+            PLANE(n) => xkk(0xF000, n, 0x01),
+            AUDIO => 0xF002,
+            PITCHVx(x) => xkk(0xF000, x, 0x3A),
+        };
+        word.to_be_bytes().to_vec()
+    }
+
+    // Decodes `opcode` found at `pc`. `next` is the word after it, the
+    // operand of the XO-CHIP long load.
+    pub fn decode(pc: u16, opcode: u16, next: u16) -> Result<OpCode, ChipError> {
+        use OpCode::*;
+
+        match opcode {
+            0x0000..=0x00FF => match opcode & 0x00FF {
+                0x00E0 => Ok(CLS),
+                0x00EE => Ok(RET),
+                0x00C0..=0x00CF => Ok(SCD((opcode & 0x000F) as u8)),
+                0x00FB => Ok(SCR),
+                0x00FC => Ok(SCL),
+                0x00FD => Ok(EXIT),
+                0x00FE => Ok(LOW),
+                0x00FF => Ok(HIGH),
+                _ => Ok(SYSADDR(opcode & 0x0FFF)),
+            },
+            0x1000..=0x1FFF => Ok(JP(opcode & 0x0FFF)),
+            0x2000..=0x2FFF => Ok(CALL(opcode & 0x0FFF)),
+            0x3000..=0x3FFF => Ok(SEVxByte(
+                ((opcode & 0x0F00) >> 8) as u8,
+                (opcode & 0x00FF) as u8,
+            )),
+            0x4000..=0x4FFF => Ok(SNEVxByte(
+                ((opcode & 0x0F00) >> 8) as u8,
+                (opcode & 0x00FF) as u8,
+            )),
+            0x5000..=0x5FFF => match opcode & 0x000F {
+                0x0 => Ok(SEVxVy(
+                    ((opcode & 0x0F00) >> 8) as u8,
+                    ((opcode & 0x00F0) >> 4) as u8,
+                )),
+                0x2 => Ok(LDIVxVy(
+                    ((opcode & 0x0F00) >> 8) as u8,
+                    ((opcode & 0x00F0) >> 4) as u8,
+                )),
+                0x3 => Ok(LDVxVyI(
+                    ((opcode & 0x0F00) >> 8) as u8,
+                    ((opcode & 0x00F0) >> 4) as u8,
+                )),
+                _ => Err(ChipError::InvalidOpcode { pc, opcode }),
+            },
+            0x6000..=0x6FFF => Ok(LDVxByte(
+                ((opcode & 0x0F00) >> 8) as u8,
+                (opcode & 0x00FF) as u8,
+            )),
+            0x7000..=0x7FFF => Ok(ADDVxByte(
+                ((opcode & 0x0F00) >> 8) as u8,
+                (opcode & 0x00FF) as u8,
+            )),
+            0x8000..=0x8FFF => match opcode & 0x000F {
+                0x0 => Ok(LDVxVy(
+                    ((opcode & 0x0F00) >> 8) as u8,
+                    ((opcode & 0x00F0) >> 4) as u8,
+                )),
+                0x1 => Ok(ORVxVy(
+                    ((opcode & 0x0F00) >> 8) as u8,
+                    ((opcode & 0x00F0) >> 4) as u8,
+                )),
+                0x2 => Ok(ANDVxVy(
+                    ((opcode & 0x0F00) >> 8) as u8,
+                    ((opcode & 0x00F0) >> 4) as u8,
+                )),
+                0x3 => Ok(XORVxVy(
+                    ((opcode & 0x0F00) >> 8) as u8,
+                    ((opcode & 0x00F0) >> 4) as u8,
+                )),
+                0x4 => Ok(ADDVxVy(
+                    ((opcode & 0x0F00) >> 8) as u8,
+                    ((opcode & 0x00F0) >> 4) as u8,
+                )),
+                0x5 => Ok(SUBVxVy(
+                    ((opcode & 0x0F00) >> 8) as u8,
+                    ((opcode & 0x00F0) >> 4) as u8,
+                )),
+                0x6 => Ok(SHRVyVx(
+                    ((opcode & 0x0F00) >> 8) as u8,
+                    ((opcode & 0x00F0) >> 4) as u8,
+                )),
+                0x7 => Ok(SUBNVyVx(
+                    ((opcode & 0x0F00) >> 8) as u8,
+                    ((opcode & 0x00F0) >> 4) as u8,
+                )),
+                0xE => Ok(SHLVyVx(
+                    ((opcode & 0x0F00) >> 8) as u8,
+                    ((opcode & 0x00F0) >> 4) as u8,
+                )),
+                _ => Err(ChipError::InvalidOpcode { pc, opcode }),
+            },
+            0x9000..=0x9FFF => Ok(SNEVxVy(
+                ((opcode & 0x0F00) >> 8) as u8,
+                ((opcode & 0x00F0) >> 4) as u8,
+            )),
+            0xA000..=0xAFFF => Ok(LDI(opcode & 0x0FFF)),
+            0xB000..=0xBFFF => Ok(JP0(opcode & 0x0FFF)),
+            0xC000..=0xCFFF => Ok(RND(((opcode & 0x0F00) >> 8) as u8, (opcode & 0x00FF) as u8)),
+            0xD000..=0xDFFF => match opcode & 0x000F {
+                0x0 => Ok(DRWVxVy0(
+                    ((opcode & 0x0F00) >> 8) as u8,
+                    ((opcode & 0x00F0) >> 4) as u8,
+                )),
+                _ => Ok(DRW(
+                    ((opcode & 0x0F00) >> 8) as u8,
+                    ((opcode & 0x00F0) >> 4) as u8,
+                    (opcode & 0x000F) as u8,
+                )),
+            },
+            0xE000..=0xEFFF => match opcode & 0x00FF {
+                0x9E => Ok(SKPVx(((opcode & 0x0F00) >> 8) as u8)),
+                0xA1 => Ok(SKNPVx(((opcode & 0x0F00) >> 8) as u8)),
+                _ => Err(ChipError::InvalidOpcode { pc, opcode }),
+            },
+
+            0xF000..=0xFFFF => match opcode & 0x00FF {
+                0x00 if opcode == 0xF000 => Ok(LDILong(next)),
+                0x02 if opcode == 0xF002 => Ok(AUDIO),
+                0x01 => Ok(PLANE(((opcode & 0x0F00) >> 8) as u8)),
+                0x07 => Ok(LDVxDT(((opcode & 0x0F00) >> 8) as u8)),
+                0x0A => Ok(LDVxK(((opcode & 0x0F00) >> 8) as u8)),
+                0x15 => Ok(LDDTVx(((opcode & 0x0F00) >> 8) as u8)),
+                0x18 => Ok(LDSTVx(((opcode & 0x0F00) >> 8) as u8)),
+                0x1E => Ok(ADDIVx(((opcode & 0x0F00) >> 8) as u8)),
+                0x29 => Ok(LDFVx(((opcode & 0x0F00) >> 8) as u8)),
+                0x33 => Ok(LDBVx(((opcode & 0x0F00) >> 8) as u8)),
+                0x55 => Ok(LDIVx(((opcode & 0x0F00) >> 8) as u8)),
+                0x65 => Ok(LDVxI(((opcode & 0x0F00) >> 8) as u8)),
+                0x30 => Ok(LDHFVx(((opcode & 0x0F00) >> 8) as u8)),
+                0x75 => Ok(LDRVx(((opcode & 0x0F00) >> 8) as u8)),
+                0x85 => Ok(LDVxR(((opcode & 0x0F00) >> 8) as u8)),
+                0x3A => Ok(PITCHVx(((opcode & 0x0F00) >> 8) as u8)),
+                _ => Err(ChipError::InvalidOpcode { pc, opcode }),
+            },
+            _ => Err(ChipError::InvalidOpcode { pc, opcode }),
+        }
+    }
+}
+
+// Formats the instruction as an assembly mnemonic in the style of Cowgod's
+// technical reference, e.g. `LD V3, 0x10` or `DRW V0, V1, 5`.
+impl Display for OpCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        use OpCode::*;
+
+        match *self {
+            CLS => write!(f, "CLS"),
+            RET => write!(f, "RET"),
+            SYSADDR(addr) => write!(f, "SYS {:#05X}", addr),
+            JP(addr) => write!(f, "JP {:#05X}", addr),
+            CALL(addr) => write!(f, "CALL {:#05X}", addr),
+            SEVxByte(x, byte) => write!(f, "SE V{:X}, {:#04X}", x, byte),
+            SNEVxByte(x, byte) => write!(f, "SNE V{:X}, {:#04X}", x, byte),
+            SEVxVy(x, y) => write!(f, "SE V{:X}, V{:X}", x, y),
+            LDVxByte(x, byte) => write!(f, "LD V{:X}, {:#04X}", x, byte),
+            ADDVxByte(x, byte) => write!(f, "ADD V{:X}, {:#04X}", x, byte),
+            LDVxVy(x, y) => write!(f, "LD V{:X}, V{:X}", x, y),
+            ORVxVy(x, y) => write!(f, "OR V{:X}, V{:X}", x, y),
+            ANDVxVy(x, y) => write!(f, "AND V{:X}, V{:X}", x, y),
+            XORVxVy(x, y) => write!(f, "XOR V{:X}, V{:X}", x, y),
+            ADDVxVy(x, y) => write!(f, "ADD V{:X}, V{:X}", x, y),
+            SUBVxVy(x, y) => write!(f, "SUB V{:X}, V{:X}", x, y),
+            SHRVyVx(x, y) => write!(f, "SHR V{:X}, V{:X}", x, y),
+            SUBNVyVx(x, y) => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            SHLVyVx(x, y) => write!(f, "SHL V{:X}, V{:X}", x, y),
+            SNEVxVy(x, y) => write!(f, "SNE V{:X}, V{:X}", x, y),
+            LDI(addr) => write!(f, "LD I, {:#05X}", addr),
+            JP0(addr) => write!(f, "JP V0, {:#05X}", addr),
+            RND(x, byte) => write!(f, "RND V{:X}, {:#04X}", x, byte),
+            DRW(x, y, n) => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            SKPVx(x) => write!(f, "SKP V{:X}", x),
+            SKNPVx(x) => write!(f, "SKNP V{:X}", x),
+            LDDTVx(x) => write!(f, "LD DT, V{:X}", x),
+            LDVxK(x) => write!(f, "LD V{:X}, K", x),
+            LDVxDT(x) => write!(f, "LD V{:X}, DT", x),
+            LDSTVx(x) => write!(f, "LD ST, V{:X}", x),
+            ADDIVx(x) => write!(f, "ADD I, V{:X}", x),
+            LDFVx(x) => write!(f, "LD F, V{:X}", x),
+            LDBVx(x) => write!(f, "LD B, V{:X}", x),
+            LDIVx(x) => write!(f, "LD [I], V{:X}", x),
+            LDVxI(x) => write!(f, "LD V{:X}, [I]", x),
+            SCD(n) => write!(f, "SCD {}", n),
+            SCR => write!(f, "SCR"),
+            SCL => write!(f, "SCL"),
+            EXIT => write!(f, "EXIT"),
+            LOW => write!(f, "LOW"),
+            HIGH => write!(f, "HIGH"),
+            DRWVxVy0(x, y) => write!(f, "DRW V{:X}, V{:X}, 0", x, y),
+            LDHFVx(x) => write!(f, "LD HF, V{:X}", x),
+            LDRVx(x) => write!(f, "LD R, V{:X}", x),
+            LDVxR(x) => write!(f, "LD V{:X}, R", x),
+            LDIVxVy(x, y) => write!(f, "LD [I], V{:X}-V{:X}", x, y),
+            LDVxVyI(x, y) => write!(f, "LD V{:X}-V{:X}, [I]", x, y),
+            LDILong(addr) => write!(f, "LD I, LONG {:#06X}", addr),
+            PLANE(n) => write!(f, "PLANE {}", n),
+            AUDIO => write!(f, "AUDIO"),
+            PITCHVx(x) => write!(f, "LD PITCH, V{:X}", x),
+        }
+    }
 }
@@ -0,0 +1,50 @@
+use clap::ValueEnum;
+
+use super::{
+    opcode::OpCode,
+    quirks::{QuirkPreset, Quirks},
+    state::{MEMORY_SIZE, XO_MEMORY_SIZE},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, ValueEnum)]
+pub enum Platform {
+    #[default]
+    #[value(name = "chip8")]
+    Chip8,
+    #[value(name = "schip")]
+    Schip,
+    #[value(name = "xo-chip")]
+    XoChip,
+}
+
+impl Platform {
+    pub fn memory_size(&self) -> usize {
+        match self {
+            Platform::XoChip => XO_MEMORY_SIZE,
+            _ => MEMORY_SIZE,
+        }
+    }
+
+    // Whether the platform implements the instruction. The SUPER-CHIP
+    // instructions are also part of XO-CHIP.
+    pub fn supports(&self, opcode: &OpCode) -> bool {
+        use OpCode::*;
+
+        match opcode {
+            SCD(_) | SCR | SCL | EXIT | LOW | HIGH | DRWVxVy0(..) | LDHFVx(_) | LDRVx(_)
+            | LDVxR(_) => *self != Platform::Chip8,
+            LDIVxVy(..) | LDVxVyI(..) | LDILong(_) | PLANE(_) | AUDIO | PITCHVx(_) => {
+                *self == Platform::XoChip
+            }
+            _ => true,
+        }
+    }
+
+    pub fn default_quirks(&self) -> Quirks {
+        match self {
+            Platform::Chip8 => Quirks::preset(QuirkPreset::Chip8),
+            Platform::Schip => Quirks::preset(QuirkPreset::Schip),
+            Platform::XoChip => Quirks::preset(QuirkPreset::XoChip),
+        }
+    }
+}
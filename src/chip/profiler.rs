@@ -0,0 +1,245 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write,
+};
+
+use super::{opcode::OpCode, state::PROGRAM_START};
+
+// Rows shown per table of the text report.
+const REPORT_ROWS: usize = 10;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SubroutineStats {
+    pub calls: u64,
+    pub inclusive: u64, // Instructions between the CALL and its RET
+    pub exclusive: u64, // Instructions executed by the subroutine itself
+}
+
+// Collects execution statistics for the profiler. Time is measured in
+// executed instructions, so results do not depend on the host. The program
+// start counts as a subroutine that is never called.
+#[derive(Debug, Default)]
+pub struct Profiler {
+    instructions: u64,
+    addresses: BTreeMap<u16, (u64, Option<OpCode>)>,
+    opcodes: BTreeMap<String, u64>,
+    subroutines: BTreeMap<u16, SubroutineStats>,
+    call_stack: Vec<(u16, u64)>,
+    frames: u64,
+    key_wait_frames: u64,
+    delay_wait_frames: u64,
+    delay_reads: HashMap<u16, u32>,
+}
+
+// The variant name of an opcode, e.g. `DRW` for `DRW(0, 1, 5)`.
+fn variant(opcode: &OpCode) -> String {
+    let name = format!("{:?}", opcode);
+    match name.split_once('(') {
+        Some((variant, _)) => variant.to_string(),
+        None => name,
+    }
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Profiler::default()
+    }
+
+    // Records an instruction that executed at `pc`. Calls and returns are
+    // recognized by the stack pointer moving, the called subroutine being
+    // the address execution continued at.
+    pub(crate) fn record(
+        &mut self,
+        pc: u16,
+        opcode: Option<OpCode>,
+        sp_before: u8,
+        sp_after: u8,
+        pc_after: u16,
+    ) {
+        self.instructions += 1;
+        let entry = self.addresses.entry(pc).or_insert((0, opcode));
+        entry.0 += 1;
+        if let Some(opcode) = &opcode {
+            *self.opcodes.entry(variant(opcode)).or_default() += 1;
+        }
+        let current = self
+            .call_stack
+            .last()
+            .map_or(PROGRAM_START as u16, |&(entry, _)| entry);
+        self.subroutines.entry(current).or_default().exclusive += 1;
+
+        if sp_after > sp_before {
+            self.call_stack.push((pc_after, self.instructions));
+            self.subroutines.entry(pc_after).or_default().calls += 1;
+        } else if sp_after < sp_before {
+            if let Some((entry, start)) = self.call_stack.pop() {
+                self.subroutines.entry(entry).or_default().inclusive += self.instructions - start;
+            }
+        }
+
+        // Reading the delay timer from the same place twice in one frame
+        // means the ROM is spinning on it
+        if let Some(OpCode::LDVxDT(_)) = opcode {
+            *self.delay_reads.entry(pc).or_default() += 1;
+        }
+    }
+
+    // Called after every frame that ran, `key_wait` when it ended blocked on
+    // an FX0A key wait.
+    pub(crate) fn end_frame(&mut self, key_wait: bool) {
+        self.frames += 1;
+        if key_wait {
+            self.key_wait_frames += 1;
+        } else if self.delay_reads.values().any(|&reads| reads > 1) {
+            self.delay_wait_frames += 1;
+        }
+        self.delay_reads.clear();
+    }
+
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    // Executions per address, with the instruction first seen there.
+    pub fn addresses(&self) -> &BTreeMap<u16, (u64, Option<OpCode>)> {
+        &self.addresses
+    }
+
+    // Executions per `OpCode` variant.
+    pub fn opcodes(&self) -> &BTreeMap<String, u64> {
+        &self.opcodes
+    }
+
+    // Subroutine statistics. Inclusive counts only cover calls that returned.
+    pub fn subroutines(&self) -> &BTreeMap<u16, SubroutineStats> {
+        &self.subroutines
+    }
+
+    pub fn key_wait_frames(&self) -> u64 {
+        self.key_wait_frames
+    }
+
+    pub fn delay_wait_frames(&self) -> u64 {
+        self.delay_wait_frames
+    }
+
+    fn percent(&self, count: u64) -> f64 {
+        100.0 * count as f64 / self.instructions.max(1) as f64
+    }
+
+    // Human readable summary of the hottest addresses, opcodes and
+    // subroutines.
+    pub fn report(&self) -> String {
+        let mut report = String::new();
+        let frames = self.frames.max(1) as f64;
+        let _ = writeln!(
+            report,
+            "{} instructions in {} frames, {:.1} per frame",
+            self.instructions,
+            self.frames,
+            self.instructions as f64 / frames
+        );
+        let _ = writeln!(
+            report,
+            "Waiting: {} frames on a key ({:.1}%), {} frames on the delay timer ({:.1}%)",
+            self.key_wait_frames,
+            100.0 * self.key_wait_frames as f64 / frames,
+            self.delay_wait_frames,
+            100.0 * self.delay_wait_frames as f64 / frames
+        );
+
+        let _ = writeln!(report, "\nHot addresses:");
+        let mut addresses: Vec<_> = self.addresses.iter().collect();
+        addresses.sort_by_key(|(addr, (count, _))| (std::cmp::Reverse(*count), **addr));
+        for (addr, (count, opcode)) in addresses.into_iter().take(REPORT_ROWS) {
+            let text = opcode.map_or(String::from("??"), |opcode| opcode.to_string());
+            let _ = writeln!(
+                report,
+                "  {:03X}  {:>10}  {:5.1}%  {}",
+                addr,
+                count,
+                self.percent(*count),
+                text
+            );
+        }
+
+        let _ = writeln!(report, "\nOpcodes:");
+        let mut opcodes: Vec<_> = self.opcodes.iter().collect();
+        opcodes.sort_by_key(|(name, count)| (std::cmp::Reverse(**count), *name));
+        for (name, count) in opcodes.into_iter().take(REPORT_ROWS) {
+            let _ = writeln!(
+                report,
+                "  {:<10} {:>10}  {:5.1}%",
+                name,
+                count,
+                self.percent(*count)
+            );
+        }
+
+        let _ = writeln!(
+            report,
+            "\nSubroutines:       calls   inclusive   exclusive  per call"
+        );
+        let mut subroutines: Vec<_> = self.subroutines.iter().collect();
+        subroutines.sort_by_key(|(addr, stats)| (std::cmp::Reverse(stats.exclusive), **addr));
+        for (addr, stats) in subroutines.into_iter().take(REPORT_ROWS) {
+            let name = match *addr as usize {
+                PROGRAM_START => String::from("main"),
+                _ => format!("sub_{:03X}", addr),
+            };
+            let per_call = match stats.calls {
+                0 => String::from("-"),
+                calls => format!("{:.1}", stats.inclusive as f64 / calls as f64),
+            };
+            let _ = writeln!(
+                report,
+                "  {:<12} {:>10}  {:>10}  {:>10}  {:>8}",
+                name, stats.calls, stats.inclusive, stats.exclusive, per_call
+            );
+        }
+        report
+    }
+
+    pub fn to_json(&self) -> String {
+        let addresses: Vec<String> = self
+            .addresses
+            .iter()
+            .map(|(addr, (count, opcode))| {
+                let text = opcode.map_or(String::from("??"), |opcode| opcode.to_string());
+                format!(
+                    "{{\"addr\": {}, \"count\": {}, \"text\": \"{}\"}}",
+                    addr, count, text
+                )
+            })
+            .collect();
+        let opcodes: Vec<String> = self
+            .opcodes
+            .iter()
+            .map(|(name, count)| format!("\"{}\": {}", name, count))
+            .collect();
+        let subroutines: Vec<String> = self
+            .subroutines
+            .iter()
+            .map(|(addr, stats)| {
+                format!(
+                    "{{\"addr\": {}, \"calls\": {}, \"inclusive\": {}, \"exclusive\": {}}}",
+                    addr, stats.calls, stats.inclusive, stats.exclusive
+                )
+            })
+            .collect();
+        format!(
+            "{{\n  \"instructions\": {},\n  \"frames\": {},\n  \"key_wait_frames\": {},\n  \"delay_wait_frames\": {},\n  \"addresses\": [{}],\n  \"opcodes\": {{{}}},\n  \"subroutines\": [{}]\n}}\n",
+            self.instructions,
+            self.frames,
+            self.key_wait_frames,
+            self.delay_wait_frames,
+            addresses.join(", "),
+            opcodes.join(", "),
+            subroutines.join(", ")
+        )
+    }
+}
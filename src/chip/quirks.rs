@@ -0,0 +1,68 @@
+use clap::ValueEnum;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quirks {
+    pub vf_reset: bool,         // 8XY1, 8XY2 and 8XY3 reset VF to 0
+    pub memory_increment: bool, // FX55 and FX65 leave I pointing past the last register
+    pub display_wait: bool,     // DXYN waits for the next 60 Hz tick before continuing
+    pub clipping: bool,         // Sprites are clipped at the screen edges instead of wrapping
+    pub shifting: bool,         // 8XY6 and 8XYE shift Vx in place and ignore Vy
+    pub jumping: bool,          // BXNN jumps to XNN + Vx instead of NNN + V0
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum QuirkPreset {
+    #[value(name = "chip8", alias = "vip")]
+    Chip8,
+    #[value(name = "chip48")]
+    Chip48,
+    #[value(name = "schip")]
+    Schip,
+    #[value(name = "xo-chip")]
+    XoChip,
+}
+
+impl Quirks {
+    pub fn preset(preset: QuirkPreset) -> Self {
+        match preset {
+            QuirkPreset::Chip8 => Quirks {
+                vf_reset: true,
+                memory_increment: true,
+                display_wait: true,
+                clipping: true,
+                shifting: false,
+                jumping: false,
+            },
+            QuirkPreset::Chip48 => Quirks {
+                vf_reset: false,
+                memory_increment: true,
+                display_wait: false,
+                clipping: true,
+                shifting: true,
+                jumping: true,
+            },
+            QuirkPreset::Schip => Quirks {
+                vf_reset: false,
+                memory_increment: false,
+                display_wait: false,
+                clipping: true,
+                shifting: true,
+                jumping: true,
+            },
+            QuirkPreset::XoChip => Quirks {
+                vf_reset: false,
+                memory_increment: true,
+                display_wait: false,
+                clipping: false,
+                shifting: false,
+                jumping: false,
+            },
+        }
+    }
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Quirks::preset(QuirkPreset::Chip8)
+    }
+}
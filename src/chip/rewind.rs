@@ -0,0 +1,143 @@
+use std::collections::VecDeque;
+
+use super::savestate::{SaveState, SaveStateError};
+
+// Ring buffer of past states used to step the emulation backwards.
+//
+// Only the newest snapshot is kept in full, as its save state encoding. Every
+// older snapshot is stored as the run-length encoded XOR of its encoding with
+// the next newer one, so frames that only touch a few bytes of memory or the
+// display cost a few bytes each.
+pub struct RewindBuffer {
+    capacity: usize,
+    interval: u32,
+    frames: u32,
+    latest: Option<Vec<u8>>,
+    history: VecDeque<Vec<u8>>,
+}
+
+impl RewindBuffer {
+    // Keeps `seconds` of history, taking a snapshot every `interval` frames.
+    pub fn new(seconds: u32, interval: u32) -> Self {
+        let interval = interval.max(1);
+        RewindBuffer {
+            capacity: (seconds * 60 / interval) as usize,
+            interval,
+            frames: 0,
+            latest: None,
+            history: VecDeque::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.history.len() + self.latest.is_some() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.latest.is_none()
+    }
+
+    pub fn clear(&mut self) {
+        self.frames = 0;
+        self.latest = None;
+        self.history.clear();
+    }
+
+    // Called once per frame, records a snapshot every `interval` frames.
+    pub fn record(&mut self, save: &SaveState) {
+        self.frames += 1;
+        if self.frames < self.interval && self.latest.is_some() {
+            return;
+        }
+        self.frames = 0;
+
+        let bytes = save.to_bytes();
+        if let Some(previous) = self.latest.take() {
+            self.history.push_back(encode_delta(&bytes, &previous));
+            if self.history.len() > self.capacity {
+                self.history.pop_front();
+            }
+        }
+        self.latest = Some(bytes);
+    }
+
+    // Removes the newest snapshot and returns the one before it, which
+    // becomes the newest. The oldest snapshot is returned again once the
+    // history is exhausted.
+    pub fn step_back(&mut self, rom_hash: u64) -> Option<Result<SaveState, SaveStateError>> {
+        let latest = self.latest.take()?;
+        let previous = match self.history.pop_back() {
+            Some(delta) => apply_delta(&latest, &delta),
+            None => latest,
+        };
+        let save = SaveState::from_bytes(&previous, rom_hash);
+        self.latest = Some(previous);
+        self.frames = 0;
+        Some(save)
+    }
+}
+
+// Encodes `target XOR base` as a sequence of (zero run, literal run, literal
+// bytes) records with LEB128 lengths, prefixed by the length of `target`.
+fn encode_delta(base: &[u8], target: &[u8]) -> Vec<u8> {
+    let xor: Vec<u8> = target
+        .iter()
+        .enumerate()
+        .map(|(i, byte)| byte ^ base.get(i).copied().unwrap_or(0))
+        .collect();
+
+    let mut out = Vec::new();
+    write_varint(&mut out, target.len());
+    let mut pos = 0;
+    while pos < xor.len() {
+        let zeros = xor[pos..].iter().take_while(|&&byte| byte == 0).count();
+        pos += zeros;
+        let literals = xor[pos..].iter().take_while(|&&byte| byte != 0).count();
+        write_varint(&mut out, zeros);
+        write_varint(&mut out, literals);
+        out.extend_from_slice(&xor[pos..pos + literals]);
+        pos += literals;
+    }
+    out
+}
+
+fn apply_delta(base: &[u8], delta: &[u8]) -> Vec<u8> {
+    let mut pos = 0;
+    let len = read_varint(delta, &mut pos);
+    let mut out: Vec<u8> = (0..len)
+        .map(|i| base.get(i).copied().unwrap_or(0))
+        .collect();
+    let mut offset = 0;
+    while pos < delta.len() {
+        offset += read_varint(delta, &mut pos);
+        let literals = read_varint(delta, &mut pos);
+        for byte in &delta[pos..pos + literals] {
+            out[offset] ^= byte;
+            offset += 1;
+        }
+        pos += literals;
+    }
+    out
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> usize {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = data[*pos];
+        *pos += 1;
+        value |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return value;
+        }
+        shift += 7;
+    }
+}
@@ -0,0 +1,220 @@
+use std::{
+    collections::HashSet,
+    fmt::Display,
+    mem::{discriminant, Discriminant},
+    ops::Range,
+};
+
+use super::{
+    opcode::OpCode,
+    state::{AccessKind, ChipState, PROGRAM_START},
+};
+
+// Suspicious behavior noticed in strict mode. None of it stops the chip.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Finding {
+    UninitializedRead { pc: u16, addr: u16 }, // Memory read before anything wrote it
+    IndexOverflow { pc: u16, i: u16 },        // I moved past the end of memory
+    FontSprite { pc: u16, addr: u16 },        // Sprite drawn from the font area without FX29/FX30
+    SelfModifyingCode { pc: u16, addr: u16 }, // Write to memory that was executed
+    MachineCodeCall { pc: u16, addr: u16 },   // 0NNN call of a machine code routine
+    MidInstructionSkip { pc: u16, target: u16 }, // Skip into the middle of an executed instruction
+    StackDepth { pc: u16, depth: usize },     // Call nested deeper than the 16 stack entries
+}
+
+impl Finding {
+    pub fn pc(&self) -> u16 {
+        match *self {
+            Finding::UninitializedRead { pc, .. }
+            | Finding::IndexOverflow { pc, .. }
+            | Finding::FontSprite { pc, .. }
+            | Finding::SelfModifyingCode { pc, .. }
+            | Finding::MachineCodeCall { pc, .. }
+            | Finding::MidInstructionSkip { pc, .. }
+            | Finding::StackDepth { pc, .. } => pc,
+        }
+    }
+}
+
+impl Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:03X}: ", self.pc())?;
+        match *self {
+            Finding::UninitializedRead { addr, .. } => {
+                write!(f, "read of uninitialized memory at {:#05X}", addr)
+            }
+            Finding::IndexOverflow { i, .. } => {
+                write!(f, "I moved past the end of memory, now {:#06X}", i)
+            }
+            Finding::FontSprite { addr, .. } => write!(
+                f,
+                "sprite read from the font area at {:#05X} without FX29 or FX30",
+                addr
+            ),
+            Finding::SelfModifyingCode { addr, .. } => {
+                write!(f, "write to executed code at {:#05X}", addr)
+            }
+            Finding::MachineCodeCall { addr, .. } => {
+                write!(f, "call of a machine code routine at {:#05X}", addr)
+            }
+            Finding::MidInstructionSkip { target, .. } => {
+                write!(
+                    f,
+                    "skip into the middle of an instruction at {:#05X}",
+                    target
+                )
+            }
+            Finding::StackDepth { depth, .. } => {
+                write!(f, "call nested {} deep, the stack holds 16", depth)
+            }
+        }
+    }
+}
+
+// Checks every executed instruction for behavior that is undefined or that
+// a ROM most likely does not intend. Each kind of finding is reported once
+// per instruction address.
+#[derive(Debug)]
+pub struct Sanitizer {
+    initialized: Vec<bool>,
+    executed: Vec<bool>,
+    interior: Vec<bool>, // Bytes executed as anything but the first of an instruction
+    font_i: Option<u16>, // I as last set by FX29 or FX30
+    findings: Vec<Finding>,
+    seen: HashSet<(Discriminant<Finding>, u16)>,
+}
+
+impl Sanitizer {
+    pub fn new() -> Self {
+        Sanitizer {
+            initialized: Vec::new(),
+            executed: Vec::new(),
+            interior: Vec::new(),
+            font_i: None,
+            findings: Vec::new(),
+            seen: HashSet::new(),
+        }
+    }
+
+    // Forgets what ran so far. Only the `initialized` ranges of memory count
+    // as written.
+    pub(crate) fn reset(
+        &mut self,
+        memory_size: usize,
+        initialized: impl IntoIterator<Item = Range<usize>>,
+    ) {
+        self.initialized = vec![false; memory_size];
+        for range in initialized {
+            self.initialized[range].fill(true);
+        }
+        self.executed = vec![false; memory_size];
+        self.interior = vec![false; memory_size];
+        self.font_i = None;
+    }
+
+    fn report(&mut self, finding: Finding) {
+        if self.seen.insert((discriminant(&finding), finding.pc())) {
+            self.findings.push(finding);
+        }
+    }
+
+    // Checks the instruction that just ran at `pc`, given I and SP from
+    // before it ran and the state after.
+    pub(crate) fn record(
+        &mut self,
+        pc: u16,
+        opcode: Option<OpCode>,
+        i_before: u16,
+        sp_before: u8,
+        state: &ChipState,
+    ) {
+        let mut fetched = 0;
+        for access in &state.accesses {
+            let addr = access.addr as usize;
+            if addr >= self.initialized.len() {
+                continue;
+            }
+            match access.kind {
+                AccessKind::Fetch => {
+                    self.interior[addr] |= fetched > 0;
+                    self.executed[addr] = true;
+                    fetched += 1;
+                }
+                AccessKind::Write => {
+                    if self.executed[addr] {
+                        self.report(Finding::SelfModifyingCode {
+                            pc,
+                            addr: access.addr,
+                        });
+                    }
+                    self.initialized[addr] = true;
+                    continue;
+                }
+                AccessKind::SpriteRead if addr < PROGRAM_START && self.font_i != Some(i_before) => {
+                    self.report(Finding::FontSprite {
+                        pc,
+                        addr: access.addr,
+                    });
+                }
+                _ => {}
+            }
+            if !self.initialized[addr] {
+                self.report(Finding::UninitializedRead {
+                    pc,
+                    addr: access.addr,
+                });
+            }
+        }
+
+        let Some(opcode) = opcode else {
+            return;
+        };
+        match opcode {
+            OpCode::SYSADDR(addr) => self.report(Finding::MachineCodeCall { pc, addr }),
+            OpCode::CALL(_) if sp_before as usize >= state.stack.len() => {
+                self.report(Finding::StackDepth {
+                    pc,
+                    depth: sp_before as usize + 1,
+                })
+            }
+            OpCode::ADDIVx(_) | OpCode::LDIVx(_) | OpCode::LDVxI(_)
+                if state.i < i_before || state.i as usize >= state.memory.len() =>
+            {
+                self.report(Finding::IndexOverflow { pc, i: state.i })
+            }
+            OpCode::SEVxByte(..)
+            | OpCode::SNEVxByte(..)
+            | OpCode::SEVxVy(..)
+            | OpCode::SNEVxVy(..)
+            | OpCode::SKPVx(_)
+            | OpCode::SKNPVx(_) => {
+                let target = state.pc;
+                if target != pc.wrapping_add(2)
+                    && self
+                        .interior
+                        .get(target as usize)
+                        .is_some_and(|&inside| inside)
+                {
+                    self.report(Finding::MidInstructionSkip { pc, target });
+                }
+            }
+            _ => {}
+        }
+        self.font_i = match opcode {
+            OpCode::LDFVx(_) | OpCode::LDHFVx(_) => Some(state.i),
+            _ if state.i != i_before => None,
+            _ => self.font_i,
+        };
+    }
+
+    // Everything found so far, in the order it happened.
+    pub fn findings(&self) -> &[Finding] {
+        &self.findings
+    }
+}
+
+impl Default for Sanitizer {
+    fn default() -> Self {
+        Sanitizer::new()
+    }
+}
@@ -0,0 +1,254 @@
+use std::{error::Error, fmt::Display};
+
+use super::{
+    platform::Platform,
+    quirks::Quirks,
+    state::{ChipState, KeyboardHalt, HIRES_HEIGHT, HIRES_WIDTH},
+};
+
+// Save state layout, all integers little endian:
+//
+//   magic "C8SS", version u16, ROM hash u64, platform u8, quirk bits u8,
+//   RNG seed u64, RNG draws u64, RNG word position u128, followed by the
+//   `ChipState` fields in declaration order. The keypad and per-instruction
+//   flags are not saved.
+const MAGIC: &[u8; 4] = b"C8SS";
+pub const VERSION: u16 = 2;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SaveStateError {
+    BadMagic,
+    UnsupportedVersion(u16),
+    RomMismatch,
+    Truncated,
+    Corrupt(&'static str),
+}
+
+impl Display for SaveStateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SaveStateError::BadMagic => write!(f, "Not a save state file"),
+            SaveStateError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported save state version: {}", version)
+            }
+            SaveStateError::RomMismatch => write!(f, "Save state belongs to a different ROM"),
+            SaveStateError::Truncated => write!(f, "Save state is truncated"),
+            SaveStateError::Corrupt(field) => write!(f, "Save state has an invalid {}", field),
+        }
+    }
+}
+
+impl Error for SaveStateError {}
+
+// Everything needed to restore a chip besides the ROM itself.
+#[derive(Debug, Clone)]
+pub struct SaveState {
+    pub rom_hash: u64,
+    pub platform: Platform,
+    pub quirks: Quirks,
+    pub seed: u64,
+    pub rng_draws: u64,
+    // Position of the generator in its stream, restored without replaying
+    pub rng_pos: u128,
+    pub state: ChipState,
+}
+
+// 64-bit FNV-1a, used to tie save states to the ROM they were made from.
+pub fn rom_hash(rom: &[u8]) -> u64 {
+    rom.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+impl SaveState {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        let state = &self.state;
+
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&VERSION.to_le_bytes());
+        out.extend_from_slice(&self.rom_hash.to_le_bytes());
+        out.push(platform_to_byte(self.platform));
+        out.push(quirks_to_byte(&self.quirks));
+        out.extend_from_slice(&self.seed.to_le_bytes());
+        out.extend_from_slice(&self.rng_draws.to_le_bytes());
+        out.extend_from_slice(&self.rng_pos.to_le_bytes());
+
+        out.extend_from_slice(&(state.memory.len() as u32).to_le_bytes());
+        out.extend_from_slice(&state.memory);
+        out.extend_from_slice(&state.v);
+        out.extend_from_slice(&state.i.to_le_bytes());
+        out.extend_from_slice(&state.pc.to_le_bytes());
+        out.push(state.sp);
+        for addr in state.stack {
+            out.extend_from_slice(&addr.to_le_bytes());
+        }
+        out.push(state.delay_timer);
+        out.push(state.sound_timer);
+        out.extend_from_slice(&state.display);
+        out.push(state.hires as u8);
+        out.push(state.plane);
+        out.extend_from_slice(&state.rpl);
+        match state.audio_pattern {
+            Some(pattern) => {
+                out.push(1);
+                out.extend_from_slice(&pattern);
+            }
+            None => out.push(0),
+        }
+        out.push(state.pitch);
+        out.push(state.exit_flag as u8);
+        out.push(state.vblank_wait as u8);
+        match state.keyboard_halt {
+            KeyboardHalt::Resume => out.extend_from_slice(&[0, 0]),
+            KeyboardHalt::Halt(x) => out.extend_from_slice(&[1, x]),
+            KeyboardHalt::WaitForRelease(x) => out.extend_from_slice(&[2, x]),
+        }
+        out
+    }
+
+    // Parses a save state, rejecting it unless it was made from a ROM with
+    // the given hash by a compatible version.
+    pub fn from_bytes(data: &[u8], rom_hash: u64) -> Result<SaveState, SaveStateError> {
+        let mut reader = Reader { data, pos: 0 };
+
+        if reader.bytes(4)? != MAGIC {
+            return Err(SaveStateError::BadMagic);
+        }
+        let version = reader.u16()?;
+        if version != VERSION {
+            return Err(SaveStateError::UnsupportedVersion(version));
+        }
+        if reader.u64()? != rom_hash {
+            return Err(SaveStateError::RomMismatch);
+        }
+        let platform = platform_from_byte(reader.u8()?)?;
+        let quirks = quirks_from_byte(reader.u8()?);
+        let seed = reader.u64()?;
+        let rng_draws = reader.u64()?;
+        let rng_pos = reader.u128()?;
+
+        let memory_size = reader.u32()? as usize;
+        if memory_size != platform.memory_size() {
+            return Err(SaveStateError::Corrupt("memory size"));
+        }
+        let mut state = ChipState::with_memory_size(memory_size);
+        state.memory.copy_from_slice(reader.bytes(memory_size)?);
+        state.v.copy_from_slice(reader.bytes(16)?);
+        state.i = reader.u16()?;
+        state.pc = reader.u16()?;
+        state.sp = reader.u8()?;
+        if state.sp as usize > state.stack.len() {
+            return Err(SaveStateError::Corrupt("stack pointer"));
+        }
+        for addr in state.stack.iter_mut() {
+            *addr = reader.u16()?;
+        }
+        state.delay_timer = reader.u8()?;
+        state.sound_timer = reader.u8()?;
+        state
+            .display
+            .copy_from_slice(reader.bytes(HIRES_WIDTH * HIRES_HEIGHT)?);
+        state.hires = reader.u8()? != 0;
+        state.plane = reader.u8()?;
+        state.rpl.copy_from_slice(reader.bytes(16)?);
+        state.audio_pattern = match reader.u8()? {
+            0 => None,
+            _ => Some(reader.bytes(16)?.try_into().unwrap()),
+        };
+        state.pitch = reader.u8()?;
+        state.exit_flag = reader.u8()? != 0;
+        state.vblank_wait = reader.u8()? != 0;
+        state.keyboard_halt = match (reader.u8()?, reader.u8()? & 0xF) {
+            (0, _) => KeyboardHalt::Resume,
+            (1, x) => KeyboardHalt::Halt(x),
+            // The key being waited on is the one held in Vx
+            (2, x) if state.v[x as usize] < 16 => KeyboardHalt::WaitForRelease(x),
+            _ => return Err(SaveStateError::Corrupt("keyboard halt")),
+        };
+        state.draw_flag = true;
+
+        Ok(SaveState {
+            rom_hash,
+            platform,
+            quirks,
+            seed,
+            rng_draws,
+            rng_pos,
+            state,
+        })
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], SaveStateError> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or(SaveStateError::Truncated)?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, SaveStateError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, SaveStateError> {
+        Ok(u16::from_le_bytes(self.bytes(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, SaveStateError> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, SaveStateError> {
+        Ok(u64::from_le_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+
+    fn u128(&mut self) -> Result<u128, SaveStateError> {
+        Ok(u128::from_le_bytes(self.bytes(16)?.try_into().unwrap()))
+    }
+}
+
+fn platform_to_byte(platform: Platform) -> u8 {
+    match platform {
+        Platform::Chip8 => 0,
+        Platform::Schip => 1,
+        Platform::XoChip => 2,
+    }
+}
+
+fn platform_from_byte(byte: u8) -> Result<Platform, SaveStateError> {
+    match byte {
+        0 => Ok(Platform::Chip8),
+        1 => Ok(Platform::Schip),
+        2 => Ok(Platform::XoChip),
+        _ => Err(SaveStateError::Corrupt("platform")),
+    }
+}
+
+fn quirks_to_byte(quirks: &Quirks) -> u8 {
+    quirks.vf_reset as u8
+        | (quirks.memory_increment as u8) << 1
+        | (quirks.display_wait as u8) << 2
+        | (quirks.clipping as u8) << 3
+        | (quirks.shifting as u8) << 4
+        | (quirks.jumping as u8) << 5
+}
+
+fn quirks_from_byte(byte: u8) -> Quirks {
+    Quirks {
+        vf_reset: byte & 1 != 0,
+        memory_increment: byte & (1 << 1) != 0,
+        display_wait: byte & (1 << 2) != 0,
+        clipping: byte & (1 << 3) != 0,
+        shifting: byte & (1 << 4) != 0,
+        jumping: byte & (1 << 5) != 0,
+    }
+}
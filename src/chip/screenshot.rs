@@ -0,0 +1,28 @@
+use std::io::Write;
+
+use super::state::ChipState;
+
+// Colors of the four XO-CHIP plane combinations, matching the terminal.
+const PALETTE: [u8; 12] = [
+    0x00, 0x00, 0x00, // No plane
+    0xFF, 0xFF, 0xFF, // Plane 1
+    0x80, 0x80, 0x00, // Plane 2
+    0x80, 0x80, 0x80, // Both planes
+];
+
+// Writes the display as an indexed PNG with one pixel per CHIP-8 pixel, at
+// the resolution of the current mode.
+pub fn write_png(state: &ChipState, out: impl Write) -> Result<(), png::EncodingError> {
+    let (width, height) = (state.display_width(), state.display_height());
+    let mut encoder = png::Encoder::new(out, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_palette(&PALETTE[..]);
+    let mut writer = encoder.write_header()?;
+    let pixels: Vec<u8> = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (y, x)))
+        .map(|(y, x)| state.pixel(x, y) & 0x3)
+        .collect();
+    writer.write_image_data(&pixels)?;
+    writer.finish()
+}
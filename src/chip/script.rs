@@ -0,0 +1,109 @@
+use std::{error::Error, fmt::Display};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScriptError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl Display for ScriptError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for ScriptError {}
+
+// Keypad input for headless runs. Each line is a frame number followed by
+// the hex keys held from that frame on, or `-` to release all of them. A
+// `seed` line records the seed of the random number generator the input
+// was made with:
+//
+//     seed 1234
+//     # Hold 5 for ten frames, then press 4 and 6 together
+//     120 5
+//     130 -
+//     200 4 6
+//
+// Frames must be given in increasing order. Everything after `#` is a
+// comment.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct InputScript {
+    seed: Option<u64>,
+    events: Vec<(u64, [bool; 16])>,
+}
+
+impl InputScript {
+    pub fn parse(source: &str) -> Result<InputScript, ScriptError> {
+        let mut seed = None;
+        let mut events: Vec<(u64, [bool; 16])> = Vec::new();
+        for (index, line) in source.lines().enumerate() {
+            let error = |message: String| ScriptError {
+                line: index + 1,
+                message,
+            };
+            let line = line.split('#').next().unwrap_or("");
+            let mut words = line.split_whitespace();
+            let Some(frame) = words.next() else {
+                continue;
+            };
+            if frame == "seed" {
+                let (Some(value), None) = (words.next(), words.next()) else {
+                    return Err(error(String::from("expected seed NUMBER")));
+                };
+                if seed.is_some() {
+                    return Err(error(String::from("the seed is already given")));
+                }
+                let value = value
+                    .parse()
+                    .map_err(|_| error(format!("invalid seed '{}'", value)))?;
+                seed = Some(value);
+                continue;
+            }
+            let frame: u64 = frame
+                .parse()
+                .map_err(|_| error(format!("invalid frame number '{}'", frame)))?;
+            if events.last().is_some_and(|&(last, _)| last >= frame) {
+                return Err(error(format!(
+                    "frame {} is not after the previous one",
+                    frame
+                )));
+            }
+            let mut keys = [false; 16];
+            for word in words {
+                if word == "-" {
+                    continue;
+                }
+                let key = u8::from_str_radix(word, 16)
+                    .ok()
+                    .filter(|&key| key < 16)
+                    .ok_or_else(|| error(format!("invalid key '{}'", word)))?;
+                keys[key as usize] = true;
+            }
+            events.push((frame, keys));
+        }
+        Ok(InputScript { seed, events })
+    }
+
+    // Seed of the random number generator the script was recorded with.
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    // Whether the keys change in any frame after `frame`.
+    pub fn changes_after(&self, frame: u64) -> bool {
+        let keys = self.keys_at(frame);
+        self.events
+            .iter()
+            .any(|&(start, events)| start > frame && events != keys)
+    }
+
+    // Keys held during `frame`.
+    pub fn keys_at(&self, frame: u64) -> [bool; 16] {
+        self.events
+            .iter()
+            .take_while(|&&(start, _)| start <= frame)
+            .last()
+            .map_or([false; 16], |&(_, keys)| keys)
+    }
+}
@@ -1,15 +1,41 @@
-use std::{fmt::Display, time::SystemTime};
+use std::fmt::Display;
 
-#[derive(Debug, PartialEq)]
+pub const LORES_WIDTH: usize = 64;
+pub const LORES_HEIGHT: usize = 32;
+pub const HIRES_WIDTH: usize = 128;
+pub const HIRES_HEIGHT: usize = 64;
+pub const MEMORY_SIZE: usize = 0x1000;
+pub const XO_MEMORY_SIZE: usize = 0x10000;
+pub const FONT_ADDR: usize = 0x000;
+pub const BIG_FONT_ADDR: usize = 0x050;
+pub const PROGRAM_START: usize = 0x200;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum KeyboardHalt {
     Halt(u8),
     WaitForRelease(u8),
     Resume,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AccessKind {
+    Fetch,      // Read as part of an instruction
+    Read,       // Read by FX65, 5XY3 or F002
+    SpriteRead, // Read as sprite data by DXYN
+    Write,      // Written by FX33, FX55 or 5XY2
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MemoryAccess {
+    pub kind: AccessKind,
+    pub addr: u16,
+    pub old: u8, // Value before the access
+    pub new: u8, // Value after the access, equal to `old` for reads
+}
+
+#[derive(Debug, Clone)]
 pub struct ChipState {
-    pub memory: [u8; 4096],
+    pub memory: Vec<u8>,
     pub v: [u8; 16],
     pub i: u16,
     pub pc: u16,
@@ -17,41 +43,154 @@ pub struct ChipState {
     pub stack: [u16; 16],
     pub delay_timer: u8,
     pub sound_timer: u8,
-    pub display: [u8; 64 * 32],
+    pub display: [u8; HIRES_WIDTH * HIRES_HEIGHT],
+    pub hires: bool,
+    pub plane: u8,
+    pub rpl: [u8; 16],
+    pub audio_pattern: Option<[u8; 16]>,
+    pub pitch: u8,
     pub keypad: [bool; 16],
     pub jump_flag: bool,
     pub draw_flag: bool,
-    pub last_timer_update: SystemTime,
-    pub last_cycle: SystemTime,
+    pub exit_flag: bool,
+    pub vblank_wait: bool,
     pub keyboard_halt: KeyboardHalt,
+    pub accesses: Vec<MemoryAccess>, // Memory accesses of the last instruction
 }
 
 impl ChipState {
     pub fn new() -> ChipState {
+        ChipState::with_memory_size(MEMORY_SIZE)
+    }
+
+    pub fn with_memory_size(memory_size: usize) -> ChipState {
         ChipState {
-            memory: [0; 4096],
+            memory: vec![0; memory_size],
             v: [0; 16],
             i: 0,
-            pc: 0x200,
+            pc: PROGRAM_START as u16,
             sp: 0,
             stack: [0; 16],
             delay_timer: 0,
             sound_timer: 0,
-            display: [0; 64 * 32],
+            display: [0; HIRES_WIDTH * HIRES_HEIGHT],
+            hires: false,
+            plane: 1,
+            rpl: [0; 16],
+            audio_pattern: None,
+            pitch: 64,
             keypad: [false; 16],
             jump_flag: false,
             draw_flag: false,
-            last_timer_update: SystemTime::now(),
-            last_cycle: SystemTime::now(),
+            exit_flag: false,
+            vblank_wait: false,
             keyboard_halt: KeyboardHalt::Resume,
+            accesses: Vec::new(),
+        }
+    }
+
+    pub fn display_width(&self) -> usize {
+        if self.hires {
+            HIRES_WIDTH
+        } else {
+            LORES_WIDTH
+        }
+    }
+
+    pub fn display_height(&self) -> usize {
+        if self.hires {
+            HIRES_HEIGHT
+        } else {
+            LORES_HEIGHT
         }
     }
 
+    pub fn pixel(&self, x: usize, y: usize) -> u8 {
+        self.display[y * self.display_width() + x]
+    }
+
+    // Approximates the tone of the XO-CHIP audio pattern buffer by counting
+    // the bit transitions of the 128 sample loop at the current pitch.
+    pub fn audio_frequency(&self) -> Option<u16> {
+        let pattern = self.audio_pattern?;
+        let rate = 4000.0 * 2f64.powf((self.pitch as f64 - 64.0) / 48.0);
+        let bits: Vec<bool> = (0..128)
+            .map(|bit| pattern[bit / 8] & (0x80 >> (bit % 8)) != 0)
+            .collect();
+        let transitions = (0..128).filter(|&b| bits[b] != bits[(b + 1) % 128]).count();
+        Some((rate * transitions as f64 / 256.0) as u16)
+    }
+
     pub fn load_rom(&mut self, rom: Vec<u8>) {
         for (i, byte) in rom.iter().enumerate() {
-            self.memory[0x200 + i] = *byte;
+            self.memory[PROGRAM_START + i] = *byte;
         }
     }
+
+    // Whether everything that affects execution or output is the same,
+    // ignoring the per-frame draw flag and the access log.
+    pub fn same_machine_state(&self, other: &ChipState) -> bool {
+        self.same_registers(other) && self.display == other.display && self.memory == other.memory
+    }
+
+    // Like `same_machine_state`, leaving out the display and memory.
+    pub fn same_registers(&self, other: &ChipState) -> bool {
+        self.v == other.v
+            && self.i == other.i
+            && self.pc == other.pc
+            && self.sp == other.sp
+            && self.stack == other.stack
+            && self.delay_timer == other.delay_timer
+            && self.sound_timer == other.sound_timer
+            && self.hires == other.hires
+            && self.plane == other.plane
+            && self.rpl == other.rpl
+            && self.audio_pattern == other.audio_pattern
+            && self.pitch == other.pitch
+            && self.keypad == other.keypad
+            && self.jump_flag == other.jump_flag
+            && self.exit_flag == other.exit_flag
+            && self.vblank_wait == other.vblank_wait
+            && self.keyboard_halt == other.keyboard_halt
+    }
+
+    // Registers, timers and the display, one string of plane bits per row.
+    // Memory is left out.
+    pub fn to_json(&self) -> String {
+        let join = |values: &[String]| values.join(", ");
+        let v: Vec<String> = self.v.iter().map(|v| v.to_string()).collect();
+        let stack: Vec<String> = self.stack[..self.sp as usize]
+            .iter()
+            .map(|addr| addr.to_string())
+            .collect();
+        let display: Vec<String> = (0..self.display_height())
+            .map(|y| {
+                let row: String = (0..self.display_width())
+                    .map(|x| char::from(b'0' + (self.pixel(x, y) & 0x3)))
+                    .collect();
+                format!("\"{}\"", row)
+            })
+            .collect();
+        format!(
+            "{{\n  \"pc\": {},\n  \"i\": {},\n  \"v\": [{}],\n  \"sp\": {},\n  \"stack\": [{}],\n  \"delay_timer\": {},\n  \"sound_timer\": {},\n  \"hires\": {},\n  \"plane\": {},\n  \"display\": [\n    {}\n  ]\n}}\n",
+            self.pc,
+            self.i,
+            join(&v),
+            self.sp,
+            join(&stack),
+            self.delay_timer,
+            self.sound_timer,
+            self.hires,
+            self.plane,
+            display.join(",\n    ")
+        )
+    }
+}
+
+impl Default for ChipState {
+    fn default() -> Self {
+        ChipState::new()
+    }
 }
 
 impl Display for ChipState {
@@ -0,0 +1,104 @@
+use std::io::{self, BufRead, Write};
+
+use super::{error::ChipError, opcode::OpCode, state::ChipState};
+
+// Execution trace format, one line per instruction with the state before it
+// executes:
+//
+//   000000042 PC:0206 OP:6F05 V:00000000000000000000000000000000 I:0000 SP:0 DT:00 ST:00 LD VF, 0x05
+//
+// The instruction index is followed by fixed width fields and the mnemonic,
+// which is informational and ignored when comparing traces.
+pub(crate) fn write_line(
+    out: &mut dyn Write,
+    instruction: u64,
+    state: &ChipState,
+    opcode: u16,
+    decoded: Result<OpCode, ChipError>,
+) -> io::Result<()> {
+    let registers: String = state.v.iter().map(|v| format!("{:02X}", v)).collect();
+    let mnemonic = match decoded {
+        Ok(opcode) => opcode.to_string(),
+        Err(_) => String::from("INVALID"),
+    };
+    writeln!(
+        out,
+        "{:09} PC:{:04X} OP:{:04X} V:{} I:{:04X} SP:{:X} DT:{:02X} ST:{:02X} {}",
+        instruction,
+        state.pc,
+        opcode,
+        registers,
+        state.i,
+        state.sp,
+        state.delay_timer,
+        state.sound_timer,
+        mnemonic
+    )
+}
+
+// The first line where two traces differ. A side is `None` when its trace
+// ended first.
+#[derive(Debug, PartialEq)]
+pub struct Divergence {
+    pub line: usize,
+    pub left: Option<String>,
+    pub right: Option<String>,
+    pub fields: Vec<String>, // Names of the fields that differ
+}
+
+// Splits a trace line into named fields, expanding the register field into
+// `V0` to `VF`. The mnemonic is dropped.
+fn fields(line: &str) -> Vec<(String, String)> {
+    let mut tokens = line.split_whitespace();
+    let mut fields = vec![(
+        String::from("cycle"),
+        tokens.next().unwrap_or("").to_string(),
+    )];
+    for token in tokens {
+        let Some((name, value)) = token.split_once(':') else {
+            break;
+        };
+        if name == "V" {
+            for (index, chunk) in value.as_bytes().chunks(2).enumerate() {
+                fields.push((
+                    format!("V{:X}", index),
+                    String::from_utf8_lossy(chunk).into_owned(),
+                ));
+            }
+        } else {
+            fields.push((name.to_string(), value.to_string()));
+        }
+    }
+    fields
+}
+
+// Compares two traces line by line and returns the first divergence.
+pub fn diff(left: impl BufRead, right: impl BufRead) -> io::Result<Option<Divergence>> {
+    let mut left = left.lines();
+    let mut right = right.lines();
+    let mut line = 0;
+    loop {
+        line += 1;
+        let (l, r) = (left.next().transpose()?, right.next().transpose()?);
+        let differs = match (&l, &r) {
+            (None, None) => return Ok(None),
+            (Some(l), Some(r)) => {
+                let (l, r) = (fields(l), fields(r));
+                let length = l.len().max(r.len());
+                (0..length)
+                    .filter(|&index| l.get(index) != r.get(index))
+                    .map(|index| l.get(index).or(r.get(index)).unwrap().0.clone())
+                    .collect()
+            }
+            _ => vec![],
+        };
+        if l.is_none() || r.is_none() || !differs.is_empty() {
+            return Ok(Some(Divergence {
+                line,
+                left: l,
+                right: r,
+                fields: differs,
+            }));
+        }
+    }
+}
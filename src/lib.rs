@@ -0,0 +1,26 @@
+pub mod chip;
+
+pub use chip::{
+    assembler::{assemble, AssembleError},
+    builder::ChipBuilder,
+    cfg::{analyze, Cfg, Issue},
+    conformance::{run_suite, CaseResult, Outcome},
+    coverage::Coverage,
+    debugger::{DebugCommand, Debugger, WatchHit, Watchpoint},
+    disasm::{disassemble, Disassembly},
+    error::{ChipError, TrapPolicy},
+    expr::{Expr, ExprError},
+    frontend::{AudioSink, Frontend, InputSource, KeyboardEvent, VideoSink},
+    opcode::OpCode,
+    platform::Platform,
+    profiler::{Profiler, SubroutineStats},
+    quirks::{QuirkPreset, Quirks},
+    rewind::RewindBuffer,
+    sanitizer::{Finding, Sanitizer},
+    savestate::{SaveState, SaveStateError},
+    screenshot::write_png,
+    script::{InputScript, ScriptError},
+    state::{AccessKind, ChipState, MemoryAccess},
+    trace::{diff as trace_diff, Divergence},
+    Chip, FrameOutput, Idle, RunResult,
+};
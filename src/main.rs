@@ -1,35 +1,647 @@
-use clap::Parser;
+use std::{
+    error::Error,
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use chip8_rust::{
+    analyze, assemble, disassemble, run_suite, trace_diff, write_png, Chip, Expr, InputScript,
+    Outcome, Platform, QuirkPreset, Quirks, RunResult, TrapPolicy, Watchpoint,
+};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 
-mod chip;
 mod terminal;
 
 #[derive(Parser)]
-#[command(version, about, author)]
-struct Opts {
-    #[arg(short, long, help = "Path to the ROM file")]
+#[command(
+    version,
+    about,
+    author,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    // Running a ROM is the default when no subcommand is given
+    #[command(flatten)]
+    run: Option<RunOpts>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    #[command(about = "Run a ROM in the terminal, the default")]
+    Run(Box<RunOpts>),
+    #[command(about = "Print the disassembly of a ROM")]
+    Disasm(DisasmOpts),
+    #[command(about = "Assemble Octo source into a ROM")]
+    Asm(AsmOpts),
+    #[command(about = "Report the first difference between two execution traces")]
+    TraceDiff(TraceDiffOpts),
+    #[command(about = "Analyze the control flow of a ROM and report problems")]
+    Cfg(CfgOpts),
+    #[command(about = "Run the conformance ROMs and compare their displays to golden images")]
+    Selftest(SelftestOpts),
+}
+
+#[derive(Args)]
+struct SelftestOpts {
+    #[arg(
+        long,
+        default_value = "tests/conformance",
+        help = "Directory holding manifest.txt, the ROMs and the golden images"
+    )]
+    dir: PathBuf,
+    #[arg(long, help = "Record the current displays as the golden images")]
+    bless: bool,
+}
+
+#[derive(Args)]
+struct CfgOpts {
+    #[arg(help = "Path to the ROM file")]
+    rom: PathBuf,
+    #[arg(
+        short,
+        long,
+        value_enum,
+        default_value = "chip8",
+        help = "Platform whose instructions are decoded"
+    )]
+    platform: Platform,
+    #[arg(long, help = "Write the control flow graph as Graphviz DOT")]
+    dot: Option<PathBuf>,
+    #[arg(long, help = "Write the analysis as JSON")]
+    json: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct TraceDiffOpts {
+    #[arg(help = "Path to the first trace")]
+    left: PathBuf,
+    #[arg(help = "Path to the second trace")]
+    right: PathBuf,
+}
+
+#[derive(Args)]
+struct DisasmOpts {
+    #[arg(help = "Path to the ROM file")]
     rom: String,
-    #[arg(short, long, default_value = "500", help = "Chip8 clock speed in kHz")]
-    clock_speed: u64,
+    #[arg(
+        short,
+        long,
+        value_enum,
+        default_value = "chip8",
+        help = "Platform whose instructions are decoded, others are shown as data"
+    )]
+    platform: Platform,
+}
+
+#[derive(Args)]
+struct AsmOpts {
+    #[arg(help = "Path to the Octo source file")]
+    source: PathBuf,
+    #[arg(
+        short,
+        long,
+        help = "Path of the assembled ROM, defaults to the source with a .ch8 extension"
+    )]
+    output: Option<PathBuf>,
+    #[arg(
+        short,
+        long,
+        value_enum,
+        default_value = "chip8",
+        help = "Platform whose instructions may be used"
+    )]
+    platform: Platform,
+}
+
+#[derive(Args)]
+struct RunOpts {
+    #[arg(
+        short,
+        long,
+        help = "Path to the ROM file, .8o sources are assembled first"
+    )]
+    rom: String,
+    #[arg(
+        short,
+        long,
+        default_value = "8",
+        help = "Instructions executed per 60 Hz frame"
+    )]
+    ipf: u64,
     #[arg(short, long, default_value = "false", help = "Enable shift quirk")]
     shift_quirk: bool,
+    #[arg(
+        short,
+        long,
+        value_enum,
+        help = "Quirk preset, defaults to the one matching the platform"
+    )]
+    quirks: Option<QuirkPreset>,
+    #[arg(
+        short,
+        long,
+        value_enum,
+        default_value = "chip8",
+        help = "Platform to emulate"
+    )]
+    platform: Platform,
+    #[arg(
+        long,
+        help = "Seed for the random number generator, random and printed on exit if omitted"
+    )]
+    seed: Option<u64>,
+    #[arg(
+        long,
+        value_enum,
+        default_value = "halt",
+        help = "What to do when an instruction traps"
+    )]
+    on_trap: TrapPolicy,
+    #[arg(
+        long,
+        default_value = "10",
+        help = "Seconds of history kept for rewinding, 0 to disable"
+    )]
+    rewind_seconds: u32,
+    // Each snapshot serializes the whole state, about 12 KB or 74 KB on
+    // XO-CHIP, but only the bytes that changed since the previous snapshot
+    // are kept, run-length encoded.
+    #[arg(
+        long,
+        default_value = "4",
+        value_parser = clap::value_parser!(u32).range(1..),
+        help = "Frames between rewind snapshots"
+    )]
+    rewind_interval: u32,
+    // Off by default: history keeps a full copy of the state for every frame
+    // that runs instructions, about 12 KB or 74 KB on XO-CHIP, so 10 seconds
+    // cost up to 7 or 44 MB.
+    #[arg(
+        long,
+        default_value = "0",
+        help = "Seconds of execution history kept for stepping backwards, 0 to disable"
+    )]
+    history_seconds: u32,
+    #[arg(
+        short,
+        long = "break",
+        value_parser = parse_breakpoint,
+        help = "Pause when the PC reaches ADDR[ if COND], can be repeated"
+    )]
+    breakpoints: Vec<(u16, Option<Expr>)>,
+    #[arg(
+        short,
+        long = "watch",
+        value_parser = parse_watchpoint,
+        help = "Pause on accesses to START[-END][:r|w|rw], can be repeated"
+    )]
+    watchpoints: Vec<Watchpoint>,
+    #[arg(
+        short = 'e',
+        long = "watch-expr",
+        value_parser = Expr::parse,
+        help = "Expression shown in the debugger, e.g. \"mem[i] + v0\", can be repeated"
+    )]
+    watches: Vec<Expr>,
+    #[arg(long, help = "Log every executed instruction to the file")]
+    trace: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        num_args = 0..=1,
+        help = "Print a profile of the run on exit, and write it as JSON to FILE if given"
+    )]
+    profile: Option<Option<PathBuf>>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Write a coverage report to the file on exit"
+    )]
+    coverage: Option<PathBuf>,
+    #[arg(
+        long,
+        value_enum,
+        default_value = "annotated",
+        help = "Format of the coverage report"
+    )]
+    coverage_format: CoverageFormat,
+    #[arg(long, help = "Report undefined or suspicious ROM behavior on exit")]
+    strict: bool,
+    #[arg(long, help = "Fill memory with random garbage instead of zeroes")]
+    random_memory: bool,
+    #[arg(
+        long,
+        help = "Run without a terminal as fast as possible until the ROM exits or goes idle, with seed 0 unless --seed or the input script gives one. Exits with 0 when the frames run out, 1 on a trap, 3 when paused by a breakpoint, 4 on EXIT and 5 when idle"
+    )]
+    headless: bool,
+    #[arg(long, requires = "headless", help = "Stop after this many frames")]
+    frames: Option<u64>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        requires = "headless",
+        help = "Keypad input script with lines of FRAME KEYS..., `-` releasing all keys, and optionally `seed N`"
+    )]
+    input: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        requires = "headless",
+        help = "Write the final display as PNG"
+    )]
+    dump_png: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        requires = "headless",
+        help = "Write how the run ended, the final registers and the display as JSON"
+    )]
+    dump_state: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum CoverageFormat {
+    Annotated, // Disassembly marking how every line was used
+    Lcov,      // lcov tracefile with addresses as line numbers
+}
+
+// Parses an address given in hex, with or without a 0x prefix.
+fn parse_addr(addr: &str) -> Result<u16, std::num::ParseIntError> {
+    u16::from_str_radix(addr.trim_start_matches("0x"), 16)
+}
+
+// Parses `ADDR` or `ADDR if COND`, for example `2A4 if v3 == 0x10`.
+fn parse_breakpoint(breakpoint: &str) -> Result<(u16, Option<Expr>), String> {
+    let (addr, condition) = match breakpoint.split_once(" if ") {
+        Some((addr, condition)) => (addr, Some(condition)),
+        None => (breakpoint, None),
+    };
+    let addr = parse_addr(addr.trim()).map_err(|e| e.to_string())?;
+    let condition = condition
+        .map(Expr::parse)
+        .transpose()
+        .map_err(|e| e.to_string())?;
+    Ok((addr, condition))
+}
+
+fn parse_watchpoint(watch: &str) -> Result<Watchpoint, String> {
+    let (range, kind) = watch.split_once(':').unwrap_or((watch, "rw"));
+    let (start, end) = range.split_once('-').unwrap_or((range, range));
+    let start = parse_addr(start).map_err(|e| e.to_string())?;
+    let end = parse_addr(end).map_err(|e| e.to_string())?;
+    if end < start {
+        return Err(String::from("end address is before the start address"));
+    }
+    let (read, write) = match kind {
+        "r" => (true, false),
+        "w" => (false, true),
+        "rw" => (true, true),
+        _ => return Err(format!("unknown access kind {}, expected r, w or rw", kind)),
+    };
+    Ok(Watchpoint {
+        start,
+        end,
+        read,
+        write,
+    })
 }
 
 fn main() {
-    let opts = Opts::parse();
+    let cli = Cli::parse();
+    match cli
+        .command
+        .or(cli.run.map(|opts| Command::Run(Box::new(opts))))
+    {
+        None => unreachable!("clap requires the run options without a subcommand"),
+        Some(Command::Run(opts)) => run(*opts),
+        Some(Command::Disasm(opts)) => disasm(opts),
+        Some(Command::Asm(opts)) => asm(opts),
+        Some(Command::TraceDiff(opts)) => diff_traces(opts),
+        Some(Command::Cfg(opts)) => cfg(opts),
+        Some(Command::Selftest(opts)) => selftest(opts),
+    }
+}
+
+fn disasm(opts: DisasmOpts) {
     let rom = std::fs::read(&opts.rom).unwrap_or_else(|e| panic!("Failed to read ROM file: {}", e));
+    print!("{}", disassemble(&rom, opts.platform));
+}
+
+// Assembles an Octo source file, exiting with the error message on failure.
+fn assemble_file(path: &Path, platform: Platform) -> Vec<u8> {
+    let source = std::fs::read_to_string(path)
+        .unwrap_or_else(|e| panic!("Failed to read source file: {}", e));
+    assemble(&source, platform).unwrap_or_else(|e| {
+        eprintln!("{}: {}", path.display(), e);
+        std::process::exit(1);
+    })
+}
+
+fn asm(opts: AsmOpts) {
+    let rom = assemble_file(&opts.source, opts.platform);
+    let output = opts
+        .output
+        .unwrap_or_else(|| opts.source.with_extension("ch8"));
+    std::fs::write(&output, rom).unwrap_or_else(|e| panic!("Failed to write ROM file: {}", e));
+}
+
+fn cfg(opts: CfgOpts) {
+    let rom = std::fs::read(&opts.rom).unwrap_or_else(|e| panic!("Failed to read ROM file: {}", e));
+    let cfg = analyze(&rom, opts.platform);
+    if let Some(path) = &opts.dot {
+        std::fs::write(path, cfg.to_dot()).unwrap_or_else(|e| panic!("Failed to write DOT: {}", e));
+    }
+    if let Some(path) = &opts.json {
+        std::fs::write(path, cfg.to_json())
+            .unwrap_or_else(|e| panic!("Failed to write JSON: {}", e));
+    }
+    let code: usize = cfg
+        .blocks
+        .values()
+        .flat_map(|block| &block.instructions)
+        .map(|instruction| instruction.length as usize)
+        .sum();
+    println!(
+        "{} blocks in {} functions, {} of {} bytes are code",
+        cfg.blocks.len(),
+        cfg.functions.len(),
+        code,
+        rom.len()
+    );
+    match cfg.max_stack_depth {
+        Some(depth) => println!("Maximum call depth: {}", depth),
+        None => println!("Maximum call depth: unbounded"),
+    }
+    for issue in &cfg.issues {
+        println!("warning: {}", issue);
+    }
+}
+
+fn selftest(opts: SelftestOpts) {
+    let results = run_suite(&opts.dir, opts.bless)
+        .unwrap_or_else(|e| panic!("Failed to run the conformance suite: {}", e));
+    let count = |status: fn(&Outcome) -> bool| {
+        results
+            .iter()
+            .filter(|result| status(&result.outcome))
+            .count()
+    };
+    for result in &results {
+        println!("{}", result);
+    }
+    let failed = count(|outcome| matches!(outcome, Outcome::Fail(_)));
+    println!(
+        "{} passed, {} failed, {} skipped, {} blessed",
+        count(|outcome| *outcome == Outcome::Pass),
+        failed,
+        count(|outcome| matches!(outcome, Outcome::Skipped(_))),
+        count(|outcome| *outcome == Outcome::Blessed)
+    );
+    if failed > 0 {
+        std::process::exit(1);
+    }
+}
+
+fn diff_traces(opts: TraceDiffOpts) {
+    let open = |path: &Path| {
+        let file =
+            File::open(path).unwrap_or_else(|e| panic!("Failed to open {}: {}", path.display(), e));
+        BufReader::new(file)
+    };
+    let divergence = trace_diff(open(&opts.left), open(&opts.right))
+        .unwrap_or_else(|e| panic!("Failed to read traces: {}", e));
+    let Some(divergence) = divergence else {
+        println!("Traces are identical");
+        return;
+    };
+    println!("Traces diverge at line {}", divergence.line);
+    for (path, line) in [
+        (&opts.left, divergence.left),
+        (&opts.right, divergence.right),
+    ] {
+        let line = line.unwrap_or_else(|| String::from("<end of trace>"));
+        println!("  {}: {}", path.display(), line);
+    }
+    if !divergence.fields.is_empty() {
+        println!("  differs: {}", divergence.fields.join(" "));
+    }
+    std::process::exit(1);
+}
+
+fn run(mut opts: RunOpts) {
+    let path = Path::new(&opts.rom);
+    let rom = if path.extension().is_some_and(|extension| extension == "8o") {
+        assemble_file(path, opts.platform)
+    } else {
+        std::fs::read(path).unwrap_or_else(|e| panic!("Failed to read ROM file: {}", e))
+    };
+    let mut quirks = opts
+        .quirks
+        .map(Quirks::preset)
+        .unwrap_or_else(|| opts.platform.default_quirks());
+    quirks.shifting |= opts.shift_quirk;
+    let mut builder = Chip::builder()
+        .instructions_per_frame(opts.ipf)
+        .quirks(quirks)
+        .platform(opts.platform)
+        .trap_policy(opts.on_trap)
+        .save_path(&opts.rom);
+    // Nothing can step backwards without a terminal
+    if opts.rewind_seconds > 0 && !opts.headless {
+        builder = builder.rewind(opts.rewind_seconds, opts.rewind_interval);
+    }
+    if opts.history_seconds > 0 && !opts.headless {
+        builder = builder.history(opts.history_seconds);
+    }
+    if let Some(path) = &opts.trace {
+        let file = File::create(path).unwrap_or_else(|e| panic!("Failed to create trace: {}", e));
+        builder = builder.trace(BufWriter::new(file));
+    }
+    if opts.profile.is_some() {
+        builder = builder.profile();
+    }
+    if opts.coverage.is_some() {
+        builder = builder.coverage();
+    }
+    if opts.strict {
+        builder = builder.strict();
+    }
+    if opts.random_memory {
+        builder = builder.random_memory();
+    }
+    let script = match &opts.input {
+        Some(path) => {
+            let source = std::fs::read_to_string(path)
+                .unwrap_or_else(|e| panic!("Failed to read input script: {}", e));
+            InputScript::parse(&source).unwrap_or_else(|e| {
+                eprintln!("{}: {}", path.display(), e);
+                std::process::exit(1);
+            })
+        }
+        None => InputScript::default(),
+    };
+    // The seed option wins over the one recorded in the input script
+    match opts.seed.or(script.seed()) {
+        Some(seed) => builder = builder.seed(seed),
+        None if opts.headless => builder = builder.seed(0),
+        None => {}
+    }
+    let mut chip = builder.build();
+    if !opts.headless {
+        eprintln!("Seed: {}", chip.seed());
+    }
+    if let Err(e) = chip.load_rom(rom) {
+        eprintln!("Failed to load ROM: {}", e);
+        std::process::exit(1);
+    }
+    for (addr, condition) in opts.breakpoints.drain(..) {
+        match condition {
+            Some(condition) => chip
+                .debugger_mut()
+                .add_conditional_breakpoint(addr, condition),
+            None => chip.debugger_mut().add_breakpoint(addr),
+        }
+    }
+    for watchpoint in opts.watchpoints.drain(..) {
+        chip.debugger_mut().add_watchpoint(watchpoint);
+    }
+    for expr in opts.watches.drain(..) {
+        chip.debugger_mut().add_watch(expr);
+    }
+    let result = if opts.headless {
+        Ok(run_headless(&mut chip, &opts, &script))
+    } else {
+        run_terminal(&mut chip)
+    };
+    if let Some(profiler) = chip.profiler() {
+        print!("{}", profiler.report());
+        if let Some(Some(path)) = &opts.profile {
+            std::fs::write(path, profiler.to_json())
+                .unwrap_or_else(|e| panic!("Failed to write profile: {}", e));
+        }
+    }
+    if let (Some(coverage), Some(path)) = (chip.coverage(), &opts.coverage) {
+        let report = match opts.coverage_format {
+            CoverageFormat::Annotated => coverage.annotate(chip.rom(), opts.platform),
+            CoverageFormat::Lcov => coverage.to_lcov(chip.rom(), opts.platform, &opts.rom),
+        };
+        std::fs::write(path, report).unwrap_or_else(|e| panic!("Failed to write coverage: {}", e));
+    }
+    if let Some(sanitizer) = chip.sanitizer() {
+        for finding in sanitizer.findings() {
+            eprintln!("strict: {}", finding);
+        }
+    }
+    // Repeated once the terminal is gone, so a run can be reproduced
+    if !opts.headless {
+        eprintln!(
+            "Seed: {}, rerun with --seed {} to reproduce",
+            chip.seed(),
+            chip.seed()
+        );
+    }
+    // Dropping the chip flushes the trace before a possible exit
+    drop(chip);
+    match result {
+        Err(e) => {
+            eprintln!("Terminal error: {}", e);
+            std::process::exit(1);
+        }
+        Ok(RunResult::Halted(err) | RunResult::Break(err)) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+        Ok(RunResult::Paused) => std::process::exit(3),
+        Ok(RunResult::Exited) if opts.headless => std::process::exit(4),
+        Ok(RunResult::Idle(_)) => std::process::exit(5),
+        _ => {}
+    }
+}
+
+fn run_terminal(chip: &mut Chip) -> Result<RunResult, Box<dyn Error>> {
     let mut terminal = terminal::Terminal::new();
     terminal
         .init()
         .unwrap_or_else(|e| panic!("Failed to initialize terminal: {}", e));
-    let mut chip = chip::Chip::new(opts.clock_speed, &mut terminal, opts.shift_quirk);
-    chip.load_rom(rom);
-    loop {
-        let ret = chip.run();
-        if ret == "exit" {
-            break;
+    // Pace frames to 60 Hz against the monotonic clock. When the host falls
+    // behind, the schedule restarts from now instead of running frames back
+    // to back to catch up.
+    let frame_duration = Duration::from_nanos(1_000_000_000 / 60);
+    let mut next_frame = Instant::now();
+    let result = loop {
+        let result = match chip.run(&mut terminal) {
+            Ok(result) => result,
+            Err(e) => break Err(e),
+        };
+        if let RunResult::Exited | RunResult::Quit | RunResult::Halted(_) = result {
+            break Ok(result);
         }
-    }
+        next_frame += frame_duration;
+        let now = Instant::now();
+        if next_frame > now {
+            std::thread::sleep(next_frame - now);
+        } else {
+            next_frame = now;
+        }
+    };
     terminal
         .exit()
         .unwrap_or_else(|e| panic!("Failed to exit terminal: {}", e));
+    result
+}
+
+// Runs frames back to back with scripted input until the frame limit, the
+// ROM exiting or going idle, a trap or a breakpoint, then dumps the final
+// state. The exit status is 0 when the frames ran out, 1 when an instruction
+// trapped, 3 when a breakpoint or watchpoint paused it, 4 when the ROM
+// executed the SUPER-CHIP EXIT instruction and 5 when it went idle.
+fn run_headless(chip: &mut Chip, opts: &RunOpts, script: &InputScript) -> RunResult {
+    let (result, frames) = chip.run_frames(opts.frames, script);
+    match result {
+        RunResult::Paused => {
+            eprintln!("Paused at {:#05X} after {} frames", chip.state().pc, frames)
+        }
+        RunResult::Idle(idle) => eprintln!("Idle after {} frames: {}", frames, idle),
+        _ => {}
+    }
+    if let Some(path) = &opts.dump_png {
+        let file = File::create(path).unwrap_or_else(|e| panic!("Failed to create PNG: {}", e));
+        write_png(chip.state(), BufWriter::new(file))
+            .unwrap_or_else(|e| panic!("Failed to write PNG: {}", e));
+    }
+    if let Some(path) = &opts.dump_state {
+        std::fs::write(path, state_json(chip, result, frames))
+            .unwrap_or_else(|e| panic!("Failed to write state: {}", e));
+    }
+    result
+}
+
+// The final state as written by --dump-state, led by how the run ended:
+// `outcome` is "frames", "exit", "idle", "trap" or "paused", with a `reason`
+// for the last three.
+fn state_json(chip: &Chip, result: RunResult, frames: u64) -> String {
+    let (outcome, reason) = match result {
+        RunResult::Exited => ("exit", None),
+        RunResult::Idle(idle) => ("idle", Some(idle.to_string())),
+        RunResult::Halted(err) | RunResult::Break(err) => ("trap", Some(err.to_string())),
+        RunResult::Paused => ("paused", Some(format!("at {:#05X}", chip.state().pc))),
+        RunResult::Running | RunResult::Quit => ("frames", None),
+    };
+    let reason = reason.map_or_else(String::new, |reason| {
+        format!("  \"reason\": \"{}\",\n", reason)
+    });
+    // Splice the fields into the state object after its opening brace
+    let state = chip.state().to_json();
+    format!(
+        "{{\n  \"outcome\": \"{}\",\n{}  \"frames\": {},\n{}",
+        outcome,
+        reason,
+        frames,
+        &state[2..]
+    )
 }
@@ -0,0 +1,130 @@
+use std::{error::Error, io::Write};
+
+use crossterm::{
+    cursor::{MoveTo, MoveToNextLine},
+    style::{Print, PrintStyledContent, Stylize},
+    terminal::{Clear, ClearType},
+    ExecutableCommand,
+};
+
+use chip8_rust::Chip;
+
+use super::Terminal;
+
+// First terminal row of the debugger panel, below the status lines.
+const PANEL_ROW: u16 = 37;
+// Instructions listed before and after the cursor.
+const CONTEXT: u16 = 6;
+
+const HELP: &str = "p continue  i step  j step over  k step out  b breakpoint  g run to cursor  \
+                    up/down cursor  u step back  y frame back  t run back";
+
+impl Terminal {
+    pub(super) fn draw_debug_panel(&mut self, chip: &Chip) -> Result<(), Box<dyn Error>> {
+        let state = chip.state();
+        let cursor = *self.cursor.get_or_insert(state.pc);
+
+        self.stdout.execute(MoveTo(0, PANEL_ROW))?;
+        self.stdout.execute(Clear(ClearType::FromCursorDown))?;
+        let status = match (chip.trap(), chip.debugger().watch_hit()) {
+            (Some(err), _) => format!("TRAPPED: {}", err),
+            (None, Some(hit)) => format!(
+                "WATCH: {:?} at {:04X} {:02X} -> {:02X} by PC {:03X}",
+                hit.access.kind, hit.access.addr, hit.access.old, hit.access.new, hit.pc
+            ),
+            (None, None) => String::from("PAUSED"),
+        };
+        self.stdout.execute(PrintStyledContent(status.bold()))?;
+        self.stdout.execute(MoveToNextLine(1))?;
+
+        let registers: Vec<String> = state
+            .v
+            .iter()
+            .enumerate()
+            .map(|(i, v)| format!("V{:X}:{:02X}", i, v))
+            .collect();
+        self.stdout.execute(Print(registers.join(" ")))?;
+        self.stdout.execute(MoveToNextLine(1))?;
+        let stack: Vec<String> = state.stack[..state.sp as usize]
+            .iter()
+            .map(|addr| format!("{:03X}", addr))
+            .collect();
+        self.stdout.execute(Print(format!(
+            "I:{:04X} PC:{:03X} SP:{:X} DT:{:02X} ST:{:02X} Stack: [{}]",
+            state.i,
+            state.pc,
+            state.sp,
+            state.delay_timer,
+            state.sound_timer,
+            stack.join(" ")
+        )))?;
+        self.stdout.execute(MoveToNextLine(1))?;
+
+        let first = cursor.saturating_sub(CONTEXT * 2);
+        for addr in (first..=cursor.saturating_add(CONTEXT * 2)).step_by(2) {
+            let breakpoint = chip.debugger().breakpoints().get(&addr);
+            let marker = match (addr == state.pc, breakpoint) {
+                (true, Some(_)) => ">*",
+                (true, None) => "> ",
+                (false, Some(_)) => " *",
+                (false, None) => "  ",
+            };
+            let raw = (*state.memory.get(addr as usize).unwrap_or(&0) as u16) << 8
+                | *state.memory.get(addr as usize + 1).unwrap_or(&0) as u16;
+            let decoded = match chip.decode_at(addr) {
+                Ok(opcode) => opcode.to_string(),
+                Err(_) => String::from("??"),
+            };
+            let mut line = format!("{} {:03X}  {:04X}  {}", marker, addr, raw, decoded);
+            if let Some(Some(condition)) = breakpoint {
+                line += &format!("  if {}", condition);
+            }
+            if addr == cursor {
+                self.stdout.execute(PrintStyledContent(line.reverse()))?;
+            } else {
+                self.stdout.execute(Print(line))?;
+            }
+            self.stdout.execute(MoveToNextLine(1))?;
+        }
+
+        let watchpoints: Vec<String> = chip
+            .debugger()
+            .watchpoints()
+            .iter()
+            .map(|watch| {
+                let kind = match (watch.read, watch.write) {
+                    (true, true) => "rw",
+                    (true, false) => "r",
+                    _ => "w",
+                };
+                format!("{:04X}-{:04X}:{}", watch.start, watch.end, kind)
+            })
+            .collect();
+        if !watchpoints.is_empty() {
+            self.stdout
+                .execute(Print(format!("Watch: {}", watchpoints.join(" "))))?;
+            self.stdout.execute(MoveToNextLine(1))?;
+        }
+
+        for expr in chip.debugger().watches() {
+            let value = match expr.eval(state) {
+                Ok(value) => format!("{} ({:#X})", value, value),
+                Err(err) => format!("<{}>", err),
+            };
+            self.stdout
+                .execute(Print(format!("{} = {}", expr, value)))?;
+            self.stdout.execute(MoveToNextLine(1))?;
+        }
+
+        self.stdout.execute(Print(HELP))?;
+        self.stdout.flush()?;
+        Ok(())
+    }
+
+    pub(super) fn clear_debug_panel(&mut self) -> Result<(), Box<dyn Error>> {
+        self.stdout.execute(MoveTo(0, PANEL_ROW))?;
+        self.stdout.execute(Clear(ClearType::FromCursorDown))?;
+        self.stdout.flush()?;
+        Ok(())
+    }
+}
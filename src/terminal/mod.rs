@@ -6,36 +6,71 @@ use std::{
 
 use crossterm::{
     cursor::MoveToNextLine,
-    style::Print,
+    style::{Color, Print, PrintStyledContent, Stylize},
     terminal::{disable_raw_mode, enable_raw_mode},
     ExecutableCommand,
 };
 
-use crate::chip::state::ChipState;
+use beep::beep;
+
+use chip8_rust::{AudioSink, Chip, ChipState, DebugCommand, InputSource, KeyboardEvent, VideoSink};
+
+mod debugger;
 
 pub struct Terminal {
     stdout: Stdout,
     key_state: [SystemTime; 16],
+    slot: u8,
+    message: String,
+    rewind_key: SystemTime,
+    debugging: bool,
+    cursor: Option<u16>,
 }
 
-pub enum KeyboardEvent {
-    State([bool; 16]),
-    Exit,
-    Reset,
-    Pause,
+const KEY_REPEAT_INTERVAL: u64 = 100;
+
+// Keys count as held until the terminal's key repeat should have sent
+// another press.
+fn is_held(pressed: &SystemTime) -> bool {
+    pressed
+        .elapsed()
+        .unwrap_or_else(|e| panic!("Failed to get elapsed time: {}", e))
+        .as_millis()
+        < KEY_REPEAT_INTERVAL as u128
 }
 
-const KEY_REPEAT_INTERVAL: u64 = 100;
+// Maps the XO-CHIP plane bits of a pixel to one of four colors.
+fn plane_color(pixel: u8) -> Color {
+    match pixel & 0x3 {
+        0 => Color::Reset,
+        1 => Color::White,
+        2 => Color::DarkYellow,
+        _ => Color::DarkGrey,
+    }
+}
 
 impl Terminal {
     pub fn new() -> Self {
         Self {
             stdout: std::io::stdout(),
             key_state: [SystemTime::now() - Duration::from_millis(KEY_REPEAT_INTERVAL); 16],
+            slot: 1,
+            message: String::new(),
+            rewind_key: SystemTime::now() - Duration::from_millis(KEY_REPEAT_INTERVAL),
+            debugging: false,
+            cursor: None,
         }
     }
 
     pub fn init(&mut self) -> Result<(), Box<dyn Error>> {
+        // Leave raw mode before a panic message is printed so the shell is
+        // usable afterwards.
+        let default_hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            let _ = std::io::stdout().execute(crossterm::cursor::Show);
+            let _ = disable_raw_mode();
+            default_hook(info);
+        }));
         enable_raw_mode()?;
         self.stdout.execute(crossterm::terminal::Clear(
             crossterm::terminal::ClearType::All,
@@ -55,26 +90,6 @@ impl Terminal {
         Ok(())
     }
 
-    pub fn draw(&mut self, state: &ChipState) -> Result<(), Box<dyn Error>> {
-        self.stdout.execute(crossterm::cursor::MoveTo(0, 0))?;
-        self.stdout.execute(crossterm::terminal::Clear(
-            crossterm::terminal::ClearType::All,
-        ))?;
-        self.stdout.flush()?;
-        for y in 0..32 {
-            for x in 0..64 {
-                if state.display[y * 64 + x] == 0 {
-                    self.stdout.execute(Print(" "))?;
-                } else {
-                    self.stdout.execute(Print("█"))?;
-                }
-            }
-            self.stdout.execute(MoveToNextLine(1))?;
-        }
-        self.stdout.flush()?;
-        Ok(())
-    }
-
     pub fn draw_key_state(&mut self, key_state: &[bool; 16]) -> Result<(), Box<dyn Error>> {
         self.stdout.execute(crossterm::cursor::MoveTo(0, 32))?;
         self.stdout.execute(Print("Key state: "))?;
@@ -97,7 +112,91 @@ impl Terminal {
         Ok(())
     }
 
-    pub fn get_key(&mut self) -> Result<KeyboardEvent, Box<dyn Error>> {
+    fn get_key_state(&mut self) -> [bool; 16] {
+        self.key_state
+            .iter()
+            .map(is_held)
+            .collect::<Vec<bool>>()
+            .as_slice()
+            .try_into()
+            .unwrap()
+    }
+}
+
+impl VideoSink for Terminal {
+    fn draw(&mut self, state: &ChipState) -> Result<(), Box<dyn Error>> {
+        self.stdout.execute(crossterm::cursor::MoveTo(0, 0))?;
+        self.stdout.execute(crossterm::terminal::Clear(
+            crossterm::terminal::ClearType::All,
+        ))?;
+        self.stdout.flush()?;
+        if state.hires {
+            // Pack two display rows into each terminal line using half blocks
+            // so the 128x64 screen fits in the same 32 lines as the 64x32 one.
+            for y in (0..state.display_height()).step_by(2) {
+                for x in 0..state.display_width() {
+                    // Off halves are left to the terminal background
+                    let block = match (state.pixel(x, y), state.pixel(x, y + 1)) {
+                        (0, 0) => " ".stylize(),
+                        (0, bottom) => "▄".with(plane_color(bottom)),
+                        (top, 0) => "▀".with(plane_color(top)),
+                        (top, bottom) => "▀".with(plane_color(top)).on(plane_color(bottom)),
+                    };
+                    self.stdout.execute(PrintStyledContent(block))?;
+                }
+                self.stdout.execute(MoveToNextLine(1))?;
+            }
+        } else {
+            for y in 0..state.display_height() {
+                for x in 0..state.display_width() {
+                    let pixel = state.pixel(x, y);
+                    if pixel == 0 {
+                        self.stdout.execute(Print(" "))?;
+                    } else {
+                        self.stdout
+                            .execute(PrintStyledContent("█".with(plane_color(pixel))))?;
+                    }
+                }
+                self.stdout.execute(MoveToNextLine(1))?;
+            }
+        }
+        self.stdout.flush()?;
+        Ok(())
+    }
+
+    fn draw_status(&mut self, state: &ChipState) -> Result<(), Box<dyn Error>> {
+        self.draw_key_state(&state.keypad)?;
+        self.draw_timers(state)?;
+        self.stdout.execute(crossterm::cursor::MoveTo(0, 35))?;
+        self.stdout.execute(crossterm::terminal::Clear(
+            crossterm::terminal::ClearType::CurrentLine,
+        ))?;
+        self.stdout
+            .execute(Print(format!("Slot: {} {}", self.slot, self.message)))?;
+        self.stdout.flush()?;
+        Ok(())
+    }
+
+    fn draw_debugger(&mut self, chip: &Chip) -> Result<(), Box<dyn Error>> {
+        if chip.is_paused() {
+            self.debugging = true;
+            self.draw_debug_panel(chip)?;
+        } else if self.debugging {
+            self.debugging = false;
+            self.cursor = None;
+            self.clear_debug_panel()?;
+        }
+        Ok(())
+    }
+
+    fn show_message(&mut self, message: &str) -> Result<(), Box<dyn Error>> {
+        self.message = message.to_string();
+        Ok(())
+    }
+}
+
+impl InputSource for Terminal {
+    fn get_key(&mut self) -> Result<KeyboardEvent, Box<dyn Error>> {
         if crossterm::event::poll(std::time::Duration::from_nanos(10))? {
             if let crossterm::event::Event::Key(event) = crossterm::event::read()? {
                 // Ctrl-C to exit
@@ -110,6 +209,59 @@ impl Terminal {
                     crossterm::event::KeyCode::Char('o') => return Ok(KeyboardEvent::Exit),
                     crossterm::event::KeyCode::Char('l') => return Ok(KeyboardEvent::Reset),
                     crossterm::event::KeyCode::Char('p') => return Ok(KeyboardEvent::Pause),
+                    // F1-F9 select the save slot, m saves to it and n loads it
+                    crossterm::event::KeyCode::F(slot @ 1..=9) => self.slot = slot,
+                    crossterm::event::KeyCode::Char('m') => {
+                        return Ok(KeyboardEvent::SaveState(self.slot))
+                    }
+                    crossterm::event::KeyCode::Char('n') => {
+                        return Ok(KeyboardEvent::LoadState(self.slot))
+                    }
+                    // Debugger commands, only available while paused
+                    crossterm::event::KeyCode::Char('i') if self.debugging => {
+                        self.cursor = None;
+                        return Ok(KeyboardEvent::Debug(DebugCommand::Step));
+                    }
+                    crossterm::event::KeyCode::Char('j') if self.debugging => {
+                        self.cursor = None;
+                        return Ok(KeyboardEvent::Debug(DebugCommand::StepOver));
+                    }
+                    crossterm::event::KeyCode::Char('k') if self.debugging => {
+                        self.cursor = None;
+                        return Ok(KeyboardEvent::Debug(DebugCommand::StepOut));
+                    }
+                    crossterm::event::KeyCode::Char('b') if self.debugging => {
+                        if let Some(cursor) = self.cursor {
+                            return Ok(KeyboardEvent::Debug(DebugCommand::ToggleBreakpoint(
+                                cursor,
+                            )));
+                        }
+                    }
+                    crossterm::event::KeyCode::Char('g') if self.debugging => {
+                        if let Some(cursor) = self.cursor.take() {
+                            return Ok(KeyboardEvent::Debug(DebugCommand::RunTo(cursor)));
+                        }
+                    }
+                    crossterm::event::KeyCode::Char('u') if self.debugging => {
+                        self.cursor = None;
+                        return Ok(KeyboardEvent::Debug(DebugCommand::StepBack));
+                    }
+                    crossterm::event::KeyCode::Char('y') if self.debugging => {
+                        self.cursor = None;
+                        return Ok(KeyboardEvent::Debug(DebugCommand::StepBackFrame));
+                    }
+                    crossterm::event::KeyCode::Char('t') if self.debugging => {
+                        self.cursor = None;
+                        return Ok(KeyboardEvent::Debug(DebugCommand::RunBack));
+                    }
+                    crossterm::event::KeyCode::Up if self.debugging => {
+                        self.cursor = self.cursor.map(|cursor| cursor.saturating_sub(2));
+                    }
+                    crossterm::event::KeyCode::Down if self.debugging => {
+                        self.cursor = self.cursor.map(|cursor| cursor.saturating_add(2));
+                    }
+                    // Backspace rewinds for as long as it is held
+                    crossterm::event::KeyCode::Backspace => self.rewind_key = SystemTime::now(),
                     crossterm::event::KeyCode::Char(key) => {
                         if let Some(index) = "x123qweasdzc4rfv".find(key) {
                             self.key_state[index] = SystemTime::now();
@@ -119,22 +271,16 @@ impl Terminal {
                 }
             }
         }
+        if is_held(&self.rewind_key) {
+            return Ok(KeyboardEvent::Rewind);
+        }
         // Update key states
         Ok(KeyboardEvent::State(self.get_key_state()))
     }
+}
 
-    fn get_key_state(&mut self) -> [bool; 16] {
-        self.key_state
-            .iter()
-            .map(|t| {
-                t.elapsed()
-                    .unwrap_or_else(|e| panic!("Failed to get elapsed time: {}", e))
-                    .as_millis()
-                    < KEY_REPEAT_INTERVAL as u128
-            })
-            .collect::<Vec<bool>>()
-            .as_slice()
-            .try_into()
-            .unwrap()
+impl AudioSink for Terminal {
+    fn set_tone(&mut self, frequency: Option<u16>) {
+        let _ = beep(frequency.unwrap_or(0));
     }
 }
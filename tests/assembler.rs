@@ -0,0 +1,130 @@
+use chip8_rust::{assemble, chip::disasm::Item, disassemble, OpCode, Platform};
+
+const SOURCE: &str = "
+:const speed 3
+: main
+  hires
+  plane 3
+  i := long sprite
+  v0 := 0
+  loop
+    v1 := random 0x3F
+    sprite v0 v1 4
+    v0 += speed
+    if v0 == 60 begin
+      draw
+    else
+      v2 := delay
+    end
+    while v0 != 120
+  again
+  jump0 table
+: table
+  v3 := key
+  if v3 == 1 then scroll-left
+  ;
+: draw
+  save v3 - v5
+  i := hex v2
+  bcd v2
+  audio
+  return
+: sprite
+  0xF0 0x90 0x90 0xF0
+";
+
+fn error(source: &str) -> String {
+    match assemble(source, Platform::XoChip) {
+        Ok(_) => panic!("{} should not assemble", source),
+        Err(e) => e.to_string(),
+    }
+}
+
+#[test]
+fn disassembly_encodes_back_to_the_rom() {
+    let rom = assemble(SOURCE, Platform::XoChip).unwrap();
+    let disassembly = disassemble(&rom, Platform::XoChip);
+    let mut bytes = Vec::new();
+    for line in &disassembly.lines {
+        match &line.item {
+            Item::Instruction(opcode) => {
+                assert_eq!(opcode.encode(), line.bytes, "{}", opcode);
+                bytes.extend(opcode.encode());
+            }
+            Item::Data => bytes.extend(&line.bytes),
+        }
+    }
+    assert_eq!(bytes, rom);
+
+    // Every jump and call lands on a labelled line
+    let text = disassembly.to_string();
+    for line in &disassembly.lines {
+        if let Item::Instruction(OpCode::JP(addr) | OpCode::CALL(addr)) = line.item {
+            let label = &disassembly.labels[&addr];
+            assert!(text.contains(&format!("{}:", label)), "{}", text);
+        }
+    }
+}
+
+#[test]
+fn assembles_known_encodings() {
+    let rom = assemble(
+        ": main v0 := 5 loop v0 += -1 while v0 != 0 again",
+        Platform::Chip8,
+    )
+    .unwrap();
+    // 200: 6005, 202: 70FF, 204: 4000 (skip the break unless v0 == 0),
+    // 206: 120A, 208: 1202
+    assert_eq!(
+        rom,
+        [0x60, 0x05, 0x70, 0xFF, 0x40, 0x00, 0x12, 0x0A, 0x12, 0x02]
+    );
+}
+
+#[test]
+fn block_jumps_past_0xfff_are_rejected() {
+    assert_eq!(
+        error(":org 0x1000\n: main\n  loop\n    v0 += 1\n  again"),
+        "line 5: Jump target 0x1000 is out of range"
+    );
+    assert_eq!(
+        error(":org 0xFFC\n: main\n  if v0 == 1 begin\n    v1 := 2\n  end"),
+        "line 5: Jump target 0x1002 is out of range"
+    );
+    assert_eq!(
+        error(":org 0xFFC\n: main\n  if v0 == 1 begin\n    v1 := 2\n  else\n    v1 := 3\n  end"),
+        "line 5: Jump target 0x1004 is out of range"
+    );
+    assert_eq!(
+        error(":org 0xFFC\n: main\n  loop\n    while v0 != 1\n  again"),
+        "line 5: Jump target 0x1002 is out of range"
+    );
+    assert_eq!(
+        error(": main\n  jump far\n:org 0x1000\n: far\n  v0 := 1"),
+        "line 2: Label 'far' is out of range"
+    );
+}
+
+#[test]
+fn reports_errors_with_their_line() {
+    assert_eq!(
+        error(": main\n  v0 := 1\n  bogus"),
+        "line 3: Undefined label 'bogus'"
+    );
+    assert_eq!(
+        error(": main\n  loop\n    v0 += 1"),
+        "line 2: 'loop' is never closed"
+    );
+    assert_eq!(
+        error(": main\n  end"),
+        "line 2: 'end' without 'if ... begin'"
+    );
+    assert_eq!(
+        error(": main\n: main"),
+        "line 2: Label 'main' is already defined"
+    );
+    assert_eq!(
+        error(": main\n  jump 0x1000"),
+        "line 2: Address 0x1000 is out of range"
+    );
+}
@@ -0,0 +1,72 @@
+use chip8_rust::{assemble, Chip, Platform};
+
+// Waits on the delay timer and polls a key between drawing random sprites,
+// with loops that count down within a frame, writing memory or not.
+const SOURCE: &str = "
+: main
+  v1 := 0
+  v2 := 0
+: loop
+  v0 := 7
+  delay := v0
+: wait
+  v0 := delay
+  v3 := v0
+  v3 += 1
+  if v0 != 0 then jump wait
+  i := hex v1
+  sprite v2 v2 5
+  v1 += 1
+  v2 += 3
+  v6 := 40
+: count
+  v6 += -1
+  i := 0x400
+  save v6
+  if v6 != 0 then jump count
+  v7 := 30
+: pure-count
+  v7 += -1
+  if v7 != 0 then jump pure-count
+  v4 := 5
+  if v4 key then jump loop
+  v5 := random 3
+: poll
+  if v4 -key then jump poll
+  jump loop
+";
+
+// Runs 300 frames holding key 5 on and off. Coverage makes the chip look at
+// every instruction, which turns fast-forwarding off.
+fn run(instructions_per_frame: u64, coverage: bool) -> Chip {
+    let mut builder = Chip::builder()
+        .instructions_per_frame(instructions_per_frame)
+        .seed(1);
+    if coverage {
+        builder = builder.coverage();
+    }
+    let mut chip = builder.build();
+    let rom = assemble(SOURCE, Platform::Chip8).unwrap();
+    chip.load_rom(rom).unwrap();
+    for frame in 0..300 {
+        let mut keys = [false; 16];
+        keys[5] = (frame / 40) % 2 == 0;
+        chip.step_frame(keys);
+    }
+    chip
+}
+
+#[test]
+fn fast_forward_matches_running_every_instruction() {
+    for instructions_per_frame in [7, 15, 1000, 20_000] {
+        let fast = run(instructions_per_frame, false);
+        let slow = run(instructions_per_frame, true);
+        assert!(
+            fast.state().same_machine_state(slow.state()),
+            "{} instructions per frame",
+            instructions_per_frame
+        );
+        assert_eq!(fast.instructions(), slow.instructions());
+        assert_eq!(fast.trap(), None);
+    }
+}
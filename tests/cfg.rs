@@ -0,0 +1,107 @@
+use chip8_rust::{analyze, assemble, chip::cfg::Region, Issue, Platform};
+
+#[test]
+fn flags_the_jump_not_the_instruction_it_lands_in() {
+    // 200: SNE V0, 1, 202: JP 20A, 204: LD V1, 2, 206: LD V2, 3,
+    // 208: LD I, long 20E, 20C: JP 200. The jump to 20A is seen before the
+    // fallthrough reaches the long load at 208
+    let rom = assemble(
+        ": main
+           if v0 == 1 then jump 0x20A
+           v1 := 2
+           v2 := 3
+           i := long data
+           jump main
+         : data
+           0xFF",
+        Platform::XoChip,
+    )
+    .unwrap();
+    let cfg = analyze(&rom, Platform::XoChip);
+    assert_eq!(
+        cfg.issues,
+        [Issue::MidInstructionJump {
+            from: 0x202,
+            target: 0x20A
+        }]
+    );
+    let long_load = cfg
+        .blocks
+        .values()
+        .flat_map(|block| &block.instructions)
+        .find(|instruction| instruction.addr == 0x208);
+    assert_eq!(long_load.unwrap().length, 4);
+    assert_eq!(
+        cfg.map,
+        [(0x200, 0x20D, Region::Code), (0x20E, 0x20E, Region::Data)]
+    );
+}
+
+#[test]
+fn reports_reachable_computed_jumps() {
+    // 200: LD V0, 2, 202: JP V0, 206, 204: LD V1, 1, 206: LD V1, 2
+    let rom = assemble(
+        ": main
+           v0 := 2
+           jump0 table
+         : table
+           v1 := 1
+           v1 := 2",
+        Platform::Chip8,
+    )
+    .unwrap();
+    let cfg = analyze(&rom, Platform::Chip8);
+    assert_eq!(cfg.issues, [Issue::ComputedJump { addr: 0x202 }]);
+    assert_eq!(
+        cfg.map,
+        [(0x200, 0x203, Region::Code), (0x204, 0x207, Region::Data)]
+    );
+}
+
+#[test]
+fn recursion_leaves_the_stack_depth_unbounded() {
+    // 200: CALL 204, 202: JP 200, 204: SNE V0, 0, 206: RET ...
+    let rom = assemble(
+        ": main
+           countdown
+           jump main
+         : countdown
+           if v0 == 0 then return
+           v0 += -1
+           countdown
+           return",
+        Platform::Chip8,
+    )
+    .unwrap();
+    let cfg = analyze(&rom, Platform::Chip8);
+    assert_eq!(cfg.issues, [Issue::Recursion { function: 0x204 }]);
+    assert_eq!(cfg.max_stack_depth, None);
+    assert_eq!(cfg.functions[&0x204].callees, [0x204]);
+}
+
+// A chain of `depth` subroutines, each calling the next.
+fn call_chain(depth: usize) -> Vec<u8> {
+    let mut source = String::from(": main\n  level0\n  jump main\n");
+    for level in 0..depth {
+        source += &format!(": level{}\n", level);
+        if level + 1 < depth {
+            source += &format!("  level{}\n", level + 1);
+        }
+        source += "  return\n";
+    }
+    assemble(&source, Platform::Chip8).unwrap()
+}
+
+#[test]
+fn call_chains_deeper_than_the_stack_overflow() {
+    let cfg = analyze(&call_chain(16), Platform::Chip8);
+    assert_eq!(cfg.max_stack_depth, Some(16));
+    assert_eq!(cfg.issues, []);
+
+    // main at 200, then a CALL and a RET for every level from 204 on
+    let cfg = analyze(&call_chain(18), Platform::Chip8);
+    assert_eq!(cfg.max_stack_depth, Some(18));
+    let mut chain = vec![0x200];
+    chain.extend((0..18).map(|level| 0x204 + 4 * level as u16));
+    assert_eq!(cfg.issues, [Issue::StackOverflow { depth: 18, chain }]);
+}
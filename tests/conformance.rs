@@ -0,0 +1,116 @@
+use std::path::Path;
+
+use chip8_rust::{run_suite, Outcome, QuirkPreset, Quirks};
+use clap::ValueEnum;
+
+// Runs tests/conformance/manifest.txt; `chip8-rust selftest` does the same
+// and can re-record the golden images with --bless.
+#[test]
+fn displays_match_golden_images() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/conformance");
+    let results = run_suite(&dir, false).expect("conformance suite should load");
+    let failures: Vec<_> = results
+        .iter()
+        .filter(|result| matches!(result.outcome, Outcome::Fail(_)))
+        .collect();
+    for failure in &failures {
+        println!("{}", failure);
+    }
+    let skipped = results
+        .iter()
+        .filter(|result| matches!(result.outcome, Outcome::Skipped(_)))
+        .count();
+    println!(
+        "{} of {} conformance cases skipped, their ROMs are missing",
+        skipped,
+        results.len()
+    );
+    assert!(
+        failures.is_empty(),
+        "{} of {} conformance cases failed",
+        failures.len(),
+        results.len()
+    );
+}
+
+// The standard font, to draw the expected values independently of the
+// emulator.
+const FONT: [u8; 80] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, 0x20, 0x60, 0x20, 0x20, 0x70, 0xF0, 0x10, 0xF0, 0x80, 0xF0, 0xF0,
+    0x10, 0xF0, 0x10, 0xF0, 0x90, 0x90, 0xF0, 0x10, 0x10, 0xF0, 0x80, 0xF0, 0x10, 0xF0, 0xF0, 0x80,
+    0xF0, 0x90, 0xF0, 0xF0, 0x10, 0x20, 0x40, 0x40, 0xF0, 0x90, 0xF0, 0x90, 0xF0, 0xF0, 0x90, 0xF0,
+    0x10, 0xF0, 0xF0, 0x90, 0xF0, 0x90, 0x90, 0xE0, 0x90, 0xE0, 0x90, 0xE0, 0xF0, 0x80, 0x80, 0x80,
+    0xF0, 0xE0, 0x90, 0x90, 0x90, 0xE0, 0xF0, 0x80, 0xF0, 0x80, 0xF0, 0xF0, 0x80, 0xF0, 0x80, 0x80,
+];
+
+// Values printed by roms/alu.8o, worked out by hand from the quirks.
+fn alu_values(quirks: Quirks) -> Vec<u8> {
+    let flag = if quirks.vf_reset { 0x00 } else { 0x77 };
+    let (shr, shl) = if quirks.shifting {
+        ([0x40, 1], [0x02, 1])
+    } else {
+        ([0x01, 0], [0x80, 0])
+    };
+    let mut values = vec![0x3F, flag, 0x05, flag, 0x3A, flag];
+    values.extend([0x10, 1, 0xF0, 0, 0x10, 1]);
+    values.extend(shr);
+    values.extend(shl);
+    values.extend([0x01, 0x55, 1, 0, 1, 1]);
+    values
+}
+
+// Values printed by roms/memory.8o. BNNN lands mid-table without the
+// jumping quirk, leaving V0 at 2.
+fn memory_values(quirks: Quirks) -> Vec<u8> {
+    let load = if quirks.memory_increment { 0x04 } else { 0x11 };
+    let jump = if quirks.jumping { 0xA2 } else { 0x02 };
+    vec![2, 3, 4, load, 0x44, jump, 0x1A, 3, 1, 0]
+}
+
+// Checks the hex digits in a golden image, five bytes per row as the
+// programs print them.
+fn assert_digits(golden: &str, values: &[u8], name: &str) {
+    let rows: Vec<&[u8]> = golden.lines().skip(1).map(str::as_bytes).collect();
+    for (index, value) in values.iter().enumerate() {
+        let (x, y) = ((index % 5) * 11, (index / 5) * 6);
+        for (digit, dx) in [(value >> 4, 0), (value & 0xF, 5)] {
+            for row in 0..5 {
+                for column in 0..4 {
+                    let lit = FONT[digit as usize * 5 + row] & (0x80 >> column) != 0;
+                    assert_eq!(
+                        rows[y + row][x + dx + column] == b'#',
+                        lit,
+                        "{}: byte {} should be {:02X}",
+                        name,
+                        index,
+                        value
+                    );
+                }
+            }
+        }
+    }
+}
+
+// The golden images were recorded by this emulator, so check that they show
+// the values the programs are expected to print.
+#[test]
+fn golden_images_show_expected_values() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/conformance/golden");
+    for preset in [
+        QuirkPreset::Chip8,
+        QuirkPreset::Chip48,
+        QuirkPreset::Schip,
+        QuirkPreset::XoChip,
+    ] {
+        let quirks = Quirks::preset(preset);
+        let name = preset.to_possible_value().unwrap().get_name().to_string();
+        for (program, values) in [
+            ("alu", alu_values(quirks)),
+            ("memory", memory_values(quirks)),
+        ] {
+            let path = dir.join(format!("{}.{}.txt", program, name));
+            let golden = std::fs::read_to_string(&path).unwrap();
+            assert_digits(&golden, &values, &path.display().to_string());
+        }
+    }
+}
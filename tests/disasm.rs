@@ -0,0 +1,26 @@
+use chip8_rust::{disassemble, Platform};
+
+#[test]
+fn labels_only_start_lines() {
+    // 200: JP 206, 202: CALL 20A, 204: JP 207, 206: LD I, long 20B (XO-CHIP),
+    // 20A: RET. 207 is inside an instruction, 20B inside the long load
+    let rom = [
+        0x12, 0x06, 0x22, 0x0A, 0x12, 0x07, 0xF0, 0x00, 0x02, 0x0B, 0x00, 0xEE, 0x12, 0x0B,
+    ];
+    let disassembly = disassemble(&rom, Platform::XoChip);
+    let labels: Vec<u16> = disassembly.labels.keys().copied().collect();
+    assert_eq!(labels, [0x206, 0x20A]);
+    for line in &disassembly.lines {
+        assert!(line.addr != 0x207 && line.addr != 0x20B);
+    }
+
+    let text = disassembly.to_string();
+    assert!(text.contains("JP L_206"), "{}", text);
+    assert!(text.contains("CALL sub_20A"), "{}", text);
+    assert!(text.contains("JP 0x207"), "{}", text);
+    assert!(text.contains("JP 0x20B"), "{}", text);
+    // Every label used is defined
+    for label in disassembly.labels.values() {
+        assert!(text.contains(&format!("{}:", label)), "{}", text);
+    }
+}
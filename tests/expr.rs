@@ -0,0 +1,92 @@
+use chip8_rust::{ChipState, Expr};
+
+fn eval(source: &str) -> i64 {
+    let mut state = ChipState::new();
+    state.v[3] = 0x10;
+    state.i = 0x300;
+    state.memory[0x300] = 7;
+    state.sp = 2;
+    state.stack[1] = 0x2A4;
+    state.delay_timer = 5;
+    Expr::parse(source)
+        .unwrap_or_else(|e| panic!("{}: {}", source, e))
+        .eval(&state)
+        .unwrap_or_else(|e| panic!("{}: {}", source, e))
+}
+
+fn parse_error(source: &str) -> String {
+    match Expr::parse(source) {
+        Ok(_) => panic!("{} should not parse", source),
+        Err(e) => e.to_string(),
+    }
+}
+
+#[test]
+fn operators_follow_c_precedence() {
+    assert_eq!(eval("1 + 2 * 3"), 7);
+    assert_eq!(eval("(1 + 2) * 3"), 9);
+    assert_eq!(eval("10 - 4 - 3"), 3);
+    assert_eq!(eval("17 % 5 * 2"), 4);
+    assert_eq!(eval("1 << 2 + 1"), 8);
+    assert_eq!(eval("1 + 1 < 3"), 1);
+    assert_eq!(eval("2 < 3 == 1"), 1);
+    assert_eq!(eval("6 & 3 == 3"), 0);
+    assert_eq!(eval("1 | 2 ^ 3 & 1"), 3);
+    assert_eq!(eval("0 || 1 && 0"), 0);
+    assert_eq!(eval("-2 * 3"), -6);
+    assert_eq!(eval("!0 + ~0"), 0);
+    assert_eq!(eval("--1"), 1);
+}
+
+#[test]
+fn names_read_the_state() {
+    assert_eq!(eval("v3 == 0x10 && mem[i] > 4"), 1);
+    assert_eq!(eval("V3 + v[3]"), 0x20);
+    assert_eq!(eval("stack[sp - 1]"), 0x2A4);
+    assert_eq!(eval("dt == delay_timer"), 1);
+    assert_eq!(eval("memory[0x300] * st"), 0);
+}
+
+#[test]
+fn logical_operators_short_circuit() {
+    assert_eq!(eval("0 && 1 / 0"), 0);
+    assert_eq!(eval("1 || mem[-1]"), 1);
+}
+
+#[test]
+fn evaluation_errors() {
+    let state = ChipState::new();
+    for source in [
+        "1 / 0",
+        "5 % (v0 - v0)",
+        "mem[0x10000]",
+        "v[16]",
+        "stack[-1]",
+    ] {
+        assert!(
+            Expr::parse(source).unwrap().eval(&state).is_err(),
+            "{} should fail",
+            source
+        );
+    }
+}
+
+#[test]
+fn parse_errors() {
+    assert_eq!(parse_error(""), "Unexpected end of expression");
+    assert_eq!(parse_error("1 +"), "Unexpected end of expression");
+    assert_eq!(parse_error("(1 + 2"), "Expected )");
+    assert_eq!(parse_error("mem[1"), "Expected ]");
+    assert_eq!(parse_error("mem 1"), "Expected [");
+    assert_eq!(parse_error("vg"), "Unknown name vg");
+    assert_eq!(parse_error("v10"), "Unknown name v10");
+    assert_eq!(parse_error("0x"), "Invalid number 0x");
+    assert_eq!(parse_error("12ab"), "Invalid number 12ab");
+    assert_eq!(parse_error("1 $ 2"), "Unexpected character $");
+    assert!(parse_error("1 2").starts_with("Unexpected"));
+}
+
+#[test]
+fn displays_the_source() {
+    assert_eq!(Expr::parse("  v0 == 1 ").unwrap().to_string(), "v0 == 1");
+}
@@ -0,0 +1,121 @@
+use chip8_rust::{assemble, AccessKind, Chip, ChipState, Platform, Watchpoint};
+
+// Runs down the timers while drawing random numbers and saving them to
+// memory, so undoing an instruction has to restore the registers, memory,
+// timers and the random number generator.
+const SOURCE: &str = "
+: main
+  v3 := 30
+  delay := v3
+  buzzer := v3
+  v0 := 0
+: loop
+  i := 0x300
+  v0 += 1
+  v1 := random 0xFF
+  save v1
+  jump loop
+";
+
+// 0x20C: v1 := random 0xFF, 0x20E: save v1
+const RANDOM: u16 = 0x20C;
+const SAVE: u16 = 0x20E;
+
+// A chip running one instruction per frame, so every frame boundary falls
+// between two instructions. `frames[n]` is the state after n frames, which
+// is also the state before instruction n.
+fn run(frames: usize) -> (Chip, Vec<ChipState>) {
+    let mut chip = Chip::builder()
+        .instructions_per_frame(1)
+        .history(1)
+        .seed(11)
+        .build();
+    let rom = assemble(SOURCE, Platform::Chip8).unwrap();
+    chip.load_rom(rom).unwrap();
+    let mut states = vec![chip.state().clone()];
+    for _ in 0..frames {
+        chip.step_frame([false; 16]);
+        states.push(chip.state().clone());
+    }
+    (chip, states)
+}
+
+#[test]
+fn step_back_undoes_single_steps() {
+    let (mut chip, _) = run(8);
+    chip.pause();
+    let mut states = vec![chip.state().clone()];
+    for _ in 0..12 {
+        chip.step();
+        states.push(chip.state().clone());
+    }
+    assert_eq!(chip.instructions(), 20);
+
+    // Every step back lands on the copy taken one step earlier
+    for expected in states.iter().rev().skip(1) {
+        assert!(chip.step_back());
+        assert!(chip.is_paused());
+        assert!(chip.state().same_machine_state(expected));
+    }
+    assert_eq!(chip.instructions(), 8);
+}
+
+#[test]
+fn step_back_across_a_frame_restores_the_timers() {
+    let (mut chip, states) = run(20);
+    for frames in (10..20).rev() {
+        let delay_timer = chip.state().delay_timer;
+        assert!(chip.step_back());
+        assert!(chip.state().same_machine_state(&states[frames]));
+        assert_eq!(chip.state().delay_timer, delay_timer + 1);
+        assert_eq!(chip.state().sound_timer, states[frames].sound_timer);
+    }
+
+    // Going on from there replays the same frames
+    chip.resume();
+    for expected in &states[11..] {
+        chip.step_frame([false; 16]);
+        assert!(chip.state().same_machine_state(expected));
+    }
+}
+
+#[test]
+fn run_back_lands_on_the_last_breakpoint() {
+    let (mut chip, states) = run(25);
+    chip.debugger_mut().add_breakpoint(RANDOM);
+    let last = (0..25).rev().find(|&n| states[n].pc == RANDOM).unwrap();
+    assert_eq!(last, 21);
+
+    assert!(chip.run_back());
+    assert!(chip.is_paused());
+    assert_eq!(chip.instructions(), last as u64);
+    assert!(chip.state().same_machine_state(&states[last]));
+    assert_eq!(chip.debugger().watch_hit(), None);
+}
+
+#[test]
+fn run_back_lands_before_the_last_watched_access() {
+    let (mut chip, states) = run(25);
+    chip.debugger_mut().add_watchpoint(Watchpoint {
+        start: 0x301,
+        end: 0x301,
+        read: false,
+        write: true,
+    });
+    let last = (0..25).rev().find(|&n| states[n].pc == SAVE).unwrap();
+    assert_eq!(last, 22);
+
+    assert!(chip.run_back());
+    assert_eq!(chip.instructions(), last as u64);
+    assert!(chip.state().same_machine_state(&states[last]));
+    let hit = chip.debugger().watch_hit().unwrap();
+    assert_eq!(hit.pc, SAVE);
+    assert_eq!(hit.access.addr, 0x301);
+    assert_eq!(hit.access.kind, AccessKind::Write);
+    assert_eq!(hit.access.new, states[last + 1].memory[0x301]);
+
+    // Nothing earlier to go back to without a hit in between
+    chip.debugger_mut().remove_watchpoint(0);
+    assert!(!chip.run_back());
+    assert!(chip.state().same_machine_state(&states[last]));
+}
@@ -0,0 +1,72 @@
+use chip8_rust::{assemble, Chip, Idle, InputScript, Platform, RunResult};
+
+fn run(source: &str, frames: u64) -> (RunResult, u64) {
+    let mut chip = Chip::builder().seed(0).build();
+    let rom = assemble(source, Platform::Chip8).unwrap();
+    chip.load_rom(rom).unwrap();
+    chip.run_frames(Some(frames), &InputScript::default())
+}
+
+// Waits on the delay timer between frames.
+const WAIT: &str = "
+: wait
+  delay := v0
+: wait-loop
+  v0 := delay
+  if v0 != 0 then jump wait-loop
+  return
+";
+
+#[test]
+fn jumping_to_itself_is_idle() {
+    let source = ": main\n  v0 := 1\n: loop\n  jump loop";
+    assert_eq!(
+        run(source, 1000),
+        (RunResult::Idle(Idle::SelfJump(0x202)), 2)
+    );
+}
+
+#[test]
+fn waiting_for_a_key_without_input_is_idle() {
+    let source = ": main\n  v1 := 5\n  v0 := key\n  v1 := 6";
+    assert_eq!(
+        run(source, 1000),
+        (RunResult::Idle(Idle::KeyWait(0x204)), 2)
+    );
+}
+
+#[test]
+fn waiting_on_the_timer_in_a_loop_is_idle() {
+    let source = format!(
+        ": main\n  i := hex v1\n  sprite v1 v1 5\n: loop\n  v0 := 10\n  wait\n  jump loop\n{}",
+        WAIT
+    );
+    let (result, frames) = run(&source, 1000);
+    assert!(
+        matches!(result, RunResult::Idle(Idle::Loop(_))),
+        "{:?}",
+        result
+    );
+    assert!(frames <= 60, "{}", frames);
+}
+
+#[test]
+fn blinking_keeps_running() {
+    // Draws and erases a digit every 10 frames, so the display keeps changing
+    // although the machine repeats every 20
+    let source = format!(
+        ": main\n  i := hex v1\n: loop\n  sprite v1 v1 5\n  v0 := 10\n  wait\n  jump loop\n{}",
+        WAIT
+    );
+    assert_eq!(run(&source, 300), (RunResult::Running, 300));
+}
+
+#[test]
+fn changing_memory_keeps_running() {
+    // Counts in memory once a frame, which only repeats after 256 frames
+    let source = format!(
+        ": main\n  i := 0x300\n: loop\n  v1 += 1\n  save v1\n  v0 := 1\n  wait\n  jump loop\n{}",
+        WAIT
+    );
+    assert_eq!(run(&source, 300), (RunResult::Running, 300));
+}
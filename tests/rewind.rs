@@ -0,0 +1,99 @@
+use chip8_rust::{assemble, Chip, Platform, RewindBuffer, SaveState};
+
+// Scrolls a counter across the screen, changing the display, registers and
+// memory from frame to frame.
+const SOURCE: &str = "
+: main
+  v0 := 0
+  v1 := 0
+: loop
+  i := hex v0
+  sprite v1 v1 5
+  i := 0x300
+  bcd v1
+  v0 += 1
+  v1 += 1
+  jump loop
+";
+
+fn snapshots(frames: usize) -> (u64, Vec<SaveState>) {
+    let mut chip = Chip::builder().seed(3).build();
+    let rom = assemble(SOURCE, Platform::Chip8).unwrap();
+    chip.load_rom(rom).unwrap();
+    let saves = (0..frames)
+        .map(|_| {
+            chip.step_frame([false; 16]);
+            chip.save_state()
+        })
+        .collect();
+    (chip.rom_hash(), saves)
+}
+
+#[test]
+fn steps_back_through_every_snapshot() {
+    let (hash, saves) = snapshots(20);
+    let mut rewind = RewindBuffer::new(1, 1);
+    for save in &saves {
+        rewind.record(save);
+    }
+    assert_eq!(rewind.len(), saves.len());
+
+    // The newest snapshot is dropped, every older one is rebuilt from deltas
+    for expected in saves.iter().rev().skip(1) {
+        let save = rewind.step_back(hash).unwrap().unwrap();
+        assert_eq!(save.to_bytes(), expected.to_bytes());
+    }
+    // The oldest one stays once the history is exhausted
+    let save = rewind.step_back(hash).unwrap().unwrap();
+    assert_eq!(save.to_bytes(), saves[0].to_bytes());
+    assert_eq!(rewind.len(), 1);
+}
+
+#[test]
+fn keeps_only_the_configured_snapshots() {
+    let (hash, saves) = snapshots(40);
+    // One second at one snapshot every 4 frames
+    let mut rewind = RewindBuffer::new(1, 4);
+    for save in &saves {
+        rewind.record(save);
+    }
+    assert_eq!(rewind.len(), 10);
+
+    // Snapshots are taken on the first frame and every 4th one after it
+    let mut expected = saves.iter().step_by(4).rev();
+    expected.next();
+    for expected in expected {
+        let save = rewind.step_back(hash).unwrap().unwrap();
+        assert_eq!(save.to_bytes(), expected.to_bytes());
+    }
+}
+
+#[test]
+fn nothing_to_rewind_when_empty() {
+    let mut rewind = RewindBuffer::new(1, 1);
+    assert!(rewind.is_empty());
+    assert!(rewind.step_back(0).is_none());
+}
+
+#[test]
+fn rewinding_restores_the_random_numbers() {
+    let mut chip = Chip::builder().seed(5).rewind(1, 1).build();
+    let rom = assemble(
+        ": main\n  i := 0x300\n: loop\n  v0 := random 0xFF\n  save v0\n  jump loop",
+        Platform::Chip8,
+    )
+    .unwrap();
+    chip.load_rom(rom).unwrap();
+    let mut states = Vec::new();
+    for _ in 0..6 {
+        chip.step_frame([false; 16]);
+        states.push(chip.state().clone());
+    }
+
+    // Back to the fourth frame, then the fifth is drawn again
+    assert!(chip.rewind());
+    assert!(chip.rewind());
+    assert!(chip.state().same_machine_state(&states[3]));
+    chip.step_frame([false; 16]);
+    assert!(chip.state().same_machine_state(&states[4]));
+}
@@ -0,0 +1,111 @@
+use chip8_rust::{assemble, chip::state::KeyboardHalt, Chip, Platform, SaveState, SaveStateError};
+
+// Draws random sprites in hires mode with the timers running, so most of the
+// saved fields hold something other than their initial value.
+const SOURCE: &str = "
+: main
+  hires
+  v0 := 60
+  delay := v0
+  buzzer := v0
+: loop
+  v1 := random 0x7F
+  v2 := random 0x3F
+  i := hex v1
+  sprite v1 v2 5
+  i := 0x600
+  save v3
+  jump loop
+";
+
+fn chip() -> Chip {
+    let mut chip = Chip::builder().platform(Platform::Schip).seed(7).build();
+    let rom = assemble(SOURCE, Platform::Schip).unwrap();
+    chip.load_rom(rom).unwrap();
+    for _ in 0..3 {
+        chip.step_frame([false; 16]);
+    }
+    assert_eq!(chip.trap(), None);
+    chip
+}
+
+#[test]
+fn round_trip_restores_the_chip() {
+    let mut chip = chip();
+    let bytes = chip.save_state().to_bytes();
+    let save = SaveState::from_bytes(&bytes, chip.rom_hash()).unwrap();
+    assert_eq!(save.to_bytes(), bytes);
+
+    // Both chips go on to produce the same frames, random numbers included
+    let mut restored = Chip::builder().platform(Platform::Schip).build();
+    restored.load_rom(chip.rom().to_vec()).unwrap();
+    restored.load_state(save);
+    assert!(restored.state().same_machine_state(chip.state()));
+    for _ in 0..10 {
+        chip.step_frame([false; 16]);
+        restored.step_frame([false; 16]);
+    }
+    assert!(restored.state().same_machine_state(chip.state()));
+}
+
+#[test]
+fn truncated_data_is_rejected() {
+    let chip = chip();
+    let bytes = chip.save_state().to_bytes();
+    for len in [0, 3, 10, 30, bytes.len() / 2, bytes.len() - 1] {
+        assert_eq!(
+            SaveState::from_bytes(&bytes[..len], chip.rom_hash()).unwrap_err(),
+            SaveStateError::Truncated,
+            "length {}",
+            len
+        );
+    }
+}
+
+#[test]
+fn header_mismatches_are_rejected() {
+    let chip = chip();
+    let bytes = chip.save_state().to_bytes();
+    let hash = chip.rom_hash();
+
+    let mut magic = bytes.clone();
+    magic[0] = b'X';
+    assert_eq!(
+        SaveState::from_bytes(&magic, hash).unwrap_err(),
+        SaveStateError::BadMagic
+    );
+
+    let mut version = bytes.clone();
+    version[4..6].copy_from_slice(&99u16.to_le_bytes());
+    assert_eq!(
+        SaveState::from_bytes(&version, hash).unwrap_err(),
+        SaveStateError::UnsupportedVersion(99)
+    );
+
+    assert_eq!(
+        SaveState::from_bytes(&bytes, hash ^ 1).unwrap_err(),
+        SaveStateError::RomMismatch
+    );
+
+    let mut platform = bytes.clone();
+    platform[14] = 9;
+    assert_eq!(
+        SaveState::from_bytes(&platform, hash).unwrap_err(),
+        SaveStateError::Corrupt("platform")
+    );
+}
+
+#[test]
+fn waiting_for_a_key_outside_the_keypad_is_rejected() {
+    let chip = chip();
+    let mut save = chip.save_state();
+    save.state.keyboard_halt = KeyboardHalt::WaitForRelease(2);
+    save.state.v[2] = 9;
+    assert!(SaveState::from_bytes(&save.to_bytes(), chip.rom_hash()).is_ok());
+
+    save.state.v[2] = 16;
+    assert_eq!(
+        SaveState::from_bytes(&save.to_bytes(), chip.rom_hash()).unwrap_err(),
+        SaveStateError::Corrupt("keyboard halt")
+    );
+}
@@ -0,0 +1,83 @@
+use std::{cell::RefCell, io::Write, rc::Rc};
+
+use chip8_rust::{assemble, trace_diff, Chip, Platform};
+
+// Counts in V0 and draws a random V3 every time round.
+const SOURCE: &str = "
+: main
+  v0 := 0
+: loop
+  v0 += 1
+  v3 := random 0xFF
+  jump loop
+";
+
+// Collects what the chip writes to its trace.
+#[derive(Clone, Default)]
+struct Shared(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Shared {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+fn trace(seed: u64, frames: usize) -> Vec<u8> {
+    let out = Shared::default();
+    let mut chip = Chip::builder()
+        .instructions_per_frame(10)
+        .seed(seed)
+        .trace(out.clone())
+        .build();
+    let rom = assemble(SOURCE, Platform::Chip8).unwrap();
+    chip.load_rom(rom).unwrap();
+    for _ in 0..frames {
+        chip.step_frame([false; 16]);
+    }
+    drop(chip);
+    Rc::try_unwrap(out.0).unwrap().into_inner()
+}
+
+#[test]
+fn identical_traces_do_not_diverge() {
+    let trace = trace(1, 5);
+    assert_eq!(trace.iter().filter(|&&byte| byte == b'\n').count(), 50);
+    assert_eq!(trace_diff(&trace[..], &trace[..]).unwrap(), None);
+
+    // Only the mnemonic differs, which is not compared
+    let left = "000000000 PC:0200 OP:6000 V:00000000000000000000000000000000 I:0000 SP:0 DT:00 ST:00 LD V0, 0x00\n";
+    let right = left.replace("LD V0, 0x00", "something else");
+    assert_eq!(trace_diff(left.as_bytes(), right.as_bytes()).unwrap(), None);
+}
+
+#[test]
+fn reports_the_register_that_diverges() {
+    let (left, right) = (trace(1, 5), trace(2, 5));
+    let divergence = trace_diff(&left[..], &right[..]).unwrap().unwrap();
+    // The first random number is drawn by the third instruction and shows
+    // up in the state before the fourth
+    assert_eq!(divergence.line, 4);
+    assert_eq!(divergence.fields, ["V3"]);
+    let (l, r) = (divergence.left.unwrap(), divergence.right.unwrap());
+    assert!(l.starts_with("000000003 PC:0206"), "{}", l);
+    assert!(r.starts_with("000000003 PC:0206"), "{}", r);
+}
+
+#[test]
+fn reports_the_trace_that_ends_first() {
+    let (short, long) = (trace(1, 3), trace(1, 5));
+    let divergence = trace_diff(&short[..], &long[..]).unwrap().unwrap();
+    assert_eq!(divergence.line, 31);
+    assert_eq!(divergence.left, None);
+    assert!(divergence.right.unwrap().starts_with("000000030 "));
+    assert!(divergence.fields.is_empty());
+
+    let divergence = trace_diff(&long[..], &short[..]).unwrap().unwrap();
+    assert_eq!(divergence.line, 31);
+    assert!(divergence.left.is_some());
+    assert_eq!(divergence.right, None);
+}